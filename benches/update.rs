@@ -0,0 +1,150 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+//
+//! Compares updates through `pure_cell!()` against `Cell`, `RefCell` and
+//! `&mut`, for small and large state types.
+//!
+//! Run with `cargo bench`.  See `examples/codegen.rs` for comparing the
+//! generated code.
+
+use std::{
+    cell::{Cell, RefCell},
+    mem, ptr,
+    time::Instant,
+};
+
+use pure_cell::{pure_cell, PureCell};
+
+const SMALL_ITERATIONS: u32 = 50_000_000;
+const LARGE_ITERATIONS: u32 = 1_000_000;
+
+type Large = [u64; 512];
+
+/// Hides `value` from the optimizer.
+fn black_box<T>(value: T) -> T {
+    unsafe {
+        let copy = ptr::read_volatile(&value);
+        mem::forget(value);
+        copy
+    }
+}
+
+/// Runs `f` for each iteration, printing the average time taken.
+fn bench(name: &str, iterations: u32, mut f: impl FnMut(u32)) {
+    for i in 0..iterations / 10 {
+        f(black_box(i));
+    }
+
+    let start = Instant::now();
+    for i in 0..iterations {
+        f(black_box(i));
+    }
+    let nanos = start.elapsed().as_nanos() as f64;
+
+    println!(
+        "{:<24}{:>10.3} ns/iter",
+        name,
+        nanos / f64::from(iterations)
+    );
+}
+
+fn small() {
+    let cell = PureCell::new(0u64);
+    bench("small/pure_cell!", SMALL_ITERATIONS, |i| {
+        pure_cell!(cell, u64::from(i), |state: u64, amount: u64| {
+            state = state.wrapping_add(amount);
+        })
+        .unwrap();
+    });
+    black_box(cell.into_inner());
+
+    let cell = Cell::new(0u64);
+    bench("small/Cell", SMALL_ITERATIONS, |i| {
+        cell.set(cell.get().wrapping_add(u64::from(i)));
+    });
+    black_box(cell.into_inner());
+
+    let cell = RefCell::new(0u64);
+    bench("small/RefCell", SMALL_ITERATIONS, |i| {
+        let mut value = cell.borrow_mut();
+        *value = value.wrapping_add(u64::from(i));
+    });
+    black_box(cell.into_inner());
+
+    let mut value = 0u64;
+    bench("small/&mut", SMALL_ITERATIONS, |i| {
+        value = value.wrapping_add(u64::from(i));
+    });
+    black_box(value);
+}
+
+fn large() {
+    let cell = PureCell::new([0u64; 512]);
+    bench("large/pure_cell!", LARGE_ITERATIONS, |i| {
+        pure_cell!(cell, i as usize, |state: Large, i: usize| {
+            state[i % 512] = state[i % 512].wrapping_add(1);
+        })
+        .unwrap();
+    });
+    black_box(cell.into_inner());
+
+    #[cfg(feature = "const-mut-refs")]
+    {
+        const fn increment(state: &mut Large, i: usize) {
+            state[i % 512] = state[i % 512].wrapping_add(1);
+        }
+
+        pure_cell::impl_pure_fn! {
+            struct Increment = increment as fn(&mut Large, usize) -> ();
+        }
+
+        let cell = PureCell::new([0u64; 512]);
+        bench("large/update_in_place", LARGE_ITERATIONS, |i| {
+            cell.update_in_place::<Increment>(i as usize).unwrap();
+        });
+        black_box(cell.into_inner());
+    }
+
+    let cell = PureCell::new([0u64; 512]);
+    bench("large/PureElement", LARGE_ITERATIONS, |i| {
+        let element = cell.as_slice().element(i as usize % 512).unwrap();
+        pure_cell!(element, (), |state: u64, _: ()| {
+            state = state.wrapping_add(1);
+        })
+        .unwrap();
+    });
+    black_box(cell.into_inner());
+
+    let cell = Cell::new([0u64; 512]);
+    bench("large/Cell", LARGE_ITERATIONS, |i| {
+        let mut value = cell.get();
+        value[i as usize % 512] = value[i as usize % 512].wrapping_add(1);
+        cell.set(value);
+    });
+    black_box(cell.into_inner());
+
+    let cell = RefCell::new([0u64; 512]);
+    bench("large/RefCell", LARGE_ITERATIONS, |i| {
+        let mut value = cell.borrow_mut();
+        value[i as usize % 512] = value[i as usize % 512].wrapping_add(1);
+    });
+    black_box(cell.into_inner());
+
+    let mut value = [0u64; 512];
+    bench("large/&mut", LARGE_ITERATIONS, |i| {
+        value[i as usize % 512] = value[i as usize % 512].wrapping_add(1);
+    });
+    black_box(value);
+}
+
+fn main() {
+    small();
+    large();
+}
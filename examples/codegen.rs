@@ -0,0 +1,122 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+//
+//! Non-inlined update functions, for comparing the generated code of
+//! `pure_cell!()` against `Cell`, `RefCell` and `&mut`.
+//!
+//! ```shell
+//! cargo rustc --release --example codegen -- --emit asm
+//! less target/release/examples/codegen-*.s
+//! ```
+//!
+//! CI emits LLVM IR for this example, and checks that `small_pure_cell()`,
+//! `large_pure_element()` and `large_pure_cell_in_place()` don't copy their
+//! state with `memcpy`.
+
+use std::cell::{Cell, RefCell};
+
+use pure_cell::{pure_cell, PureCell};
+
+type Large = [u64; 512];
+
+#[inline(never)]
+pub fn small_pure_cell(cell: &PureCell<u64>, amount: u64) {
+    pure_cell!(cell, amount, |state: u64, amount: u64| {
+        state = state.wrapping_add(amount);
+    })
+    .unwrap();
+}
+
+#[inline(never)]
+pub fn small_cell(cell: &Cell<u64>, amount: u64) {
+    cell.set(cell.get().wrapping_add(amount));
+}
+
+#[inline(never)]
+pub fn small_ref_cell(cell: &RefCell<u64>, amount: u64) {
+    let mut value = cell.borrow_mut();
+    *value = value.wrapping_add(amount);
+}
+
+#[inline(never)]
+pub fn small_mut(value: &mut u64, amount: u64) {
+    *value = value.wrapping_add(amount);
+}
+
+#[inline(never)]
+pub fn large_pure_cell(cell: &PureCell<Large>, index: usize) {
+    pure_cell!(cell, index, |state: Large, index: usize| {
+        state[index % 512] = state[index % 512].wrapping_add(1);
+    })
+    .unwrap();
+}
+
+#[cfg(feature = "const-mut-refs")]
+#[inline(never)]
+pub fn large_pure_cell_in_place(cell: &PureCell<Large>, index: usize) {
+    pure_cell!(cell, index, |state: &mut Large, index: usize| {
+        state[index % 512] = state[index % 512].wrapping_add(1);
+    })
+    .unwrap();
+}
+
+#[inline(never)]
+pub fn large_pure_element(cell: &PureCell<Large>, index: usize) {
+    let element = cell.as_slice().element(index % 512).unwrap();
+    pure_cell!(element, (), |state: u64, _: ()| {
+        state = state.wrapping_add(1);
+    })
+    .unwrap();
+}
+
+#[inline(never)]
+pub fn large_cell(cell: &Cell<Large>, index: usize) {
+    let mut value = cell.get();
+    value[index % 512] = value[index % 512].wrapping_add(1);
+    cell.set(value);
+}
+
+#[inline(never)]
+pub fn large_ref_cell(cell: &RefCell<Large>, index: usize) {
+    let mut value = cell.borrow_mut();
+    value[index % 512] = value[index % 512].wrapping_add(1);
+}
+
+#[inline(never)]
+pub fn large_mut(value: &mut Large, index: usize) {
+    value[index % 512] = value[index % 512].wrapping_add(1);
+}
+
+fn main() {
+    // Unknown at compile time, so the functions can't be specialized
+    let one = std::env::args().count();
+    let amount = one as u64;
+
+    let small = PureCell::new(0);
+    small_pure_cell(&small, amount);
+    small_cell(&Cell::new(0), amount);
+    small_ref_cell(&RefCell::new(0), amount);
+    small_mut(&mut 0, amount);
+
+    let large = PureCell::new([0; 512]);
+    large_pure_cell(&large, one);
+    large_pure_element(&large, one);
+    #[cfg(feature = "const-mut-refs")]
+    large_pure_cell_in_place(&large, one);
+    large_cell(&Cell::new([0; 512]), one);
+    large_ref_cell(&RefCell::new([0; 512]), one);
+    large_mut(&mut [0; 512], one);
+    let expected = if cfg!(feature = "const-mut-refs") {
+        3
+    } else {
+        2
+    };
+    assert_eq!(large.into_inner()[one], expected);
+}
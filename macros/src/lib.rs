@@ -0,0 +1,420 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+//
+//! Procedural macros for the `pure_cell` crate.
+//!
+//! Use these through `pure_cell`'s `macros` feature rather than depending on
+//! this crate directly.
+
+#![doc(
+    html_logo_url = "https://ardaku.github.io/mm/logo.svg",
+    html_favicon_url = "https://ardaku.github.io/mm/icon.svg",
+    html_root_url = "https://docs.rs/pure_cell_macros"
+)]
+#![warn(
+    anonymous_parameters,
+    missing_copy_implementations,
+    missing_debug_implementations,
+    missing_docs,
+    nonstandard_style,
+    rust_2018_idioms,
+    single_use_lifetimes,
+    trivial_casts,
+    trivial_numeric_casts,
+    unreachable_pub,
+    unused_extern_crates,
+    unused_qualifications,
+    variant_size_differences
+)]
+
+use proc_macro::{
+    Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream,
+    TokenTree,
+};
+
+/// Turns a `const fn(state: State, args: Args) -> (State, Output)` into a
+/// named pure transition.
+///
+/// For a function `do_thing`, this generates:
+///
+///  - A unit struct `DoThing` implementing `PureFn<State>`, for use with
+///    `PureCell::update::<DoThing>()`
+///  - An extension trait `DoThingExt` with a method `do_thing()` on
+///    `PureCell<State>`
+///
+/// Doc comments on the function are copied to both.
+///
+/// ```rust
+/// use pure_cell::{pure_fn, PureCell};
+///
+/// /// Adds to the count, returning the new count.
+/// #[pure_fn]
+/// const fn increment(count: u32, amount: u32) -> (u32, u32) {
+///     (count + amount, count + amount)
+/// }
+///
+/// let cell = PureCell::new(15);
+/// assert_eq!(cell.increment(2).unwrap(), 17);
+/// assert_eq!(cell.update::<Increment>(3).unwrap(), 20);
+/// ```
+///
+/// ```rust,compile_fail
+/// use pure_cell::pure_fn;
+///
+/// #[pure_fn]
+/// fn increment(count: u32, amount: u32) -> (u32, u32) {
+///     (count + amount, count + amount)
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn pure_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let expanded = match attr.into_iter().next() {
+        Some(tt) => Err(Error(tt.span(), "`#[pure_fn]` takes no arguments")),
+        None => PureFn::parse(item.clone()).map(PureFn::expand),
+    };
+    let mut output = item;
+
+    output.extend(expanded.unwrap_or_else(Error::into_tokens));
+    output
+}
+
+/// A compile error at a span.
+struct Error(Span, &'static str);
+
+impl Error {
+    fn into_tokens(self) -> TokenStream {
+        let Error(span, message) = self;
+        let mut message = Literal::string(message);
+
+        message.set_span(span);
+        [
+            TokenTree::Ident(Ident::new("compile_error", span)),
+            spanned(Punct::new('!', Spacing::Alone).into(), span),
+            spanned(
+                group(Delimiter::Parenthesis, TokenTree::from(message).into()),
+                span,
+            ),
+            spanned(Punct::new(';', Spacing::Alone).into(), span),
+        ]
+        .into_iter()
+        .collect()
+    }
+}
+
+/// Parsed `const fn` with the shape of a pure transition.
+struct PureFn {
+    docs: TokenStream,
+    vis: TokenStream,
+    name: Ident,
+    state: TokenStream,
+    args: TokenStream,
+    new: TokenStream,
+    output: TokenStream,
+}
+
+impl PureFn {
+    fn parse(item: TokenStream) -> Result<Self, Error> {
+        let mut iter = item.into_iter().peekable();
+        let mut docs = TokenStream::new();
+        let mut vis = TokenStream::new();
+
+        // Attributes, keeping only doc comments
+        while let Some(TokenTree::Punct(punct)) = iter.peek() {
+            if punct.as_char() != '#' {
+                break;
+            }
+
+            let pound = iter.next().unwrap();
+            let attr = iter.next().unwrap();
+
+            if let TokenTree::Group(ref group) = attr {
+                match group.stream().into_iter().next() {
+                    Some(TokenTree::Ident(ident))
+                        if ident.to_string() == "doc" =>
+                    {
+                        docs.extend([pound, attr]);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Visibility
+        if let Some(TokenTree::Ident(ident)) = iter.peek() {
+            if ident.to_string() == "pub" {
+                vis.extend(iter.next());
+
+                if let Some(TokenTree::Group(group)) = iter.peek() {
+                    if group.delimiter() == Delimiter::Parenthesis {
+                        vis.extend(iter.next());
+                    }
+                }
+            }
+        }
+
+        let span = iter.peek().map_or_else(Span::call_site, TokenTree::span);
+
+        expect_ident(iter.next(), "const")
+            .ok_or(Error(span, "`#[pure_fn]` requires a `const fn`"))?;
+        expect_ident(iter.next(), "fn")
+            .ok_or(Error(span, "`#[pure_fn]` requires a `const fn`"))?;
+
+        let name = match iter.next() {
+            Some(TokenTree::Ident(name)) => name,
+            _ => return Err(Error(span, "expected function name")),
+        };
+        let params = match iter.next() {
+            Some(TokenTree::Group(group))
+                if group.delimiter() == Delimiter::Parenthesis =>
+            {
+                group
+            }
+            Some(tt) => {
+                return Err(Error(
+                    tt.span(),
+                    "generic parameters aren't supported by `#[pure_fn]`",
+                ))
+            }
+            None => return Err(Error(name.span(), "expected parameters")),
+        };
+        let (state, args) = match &split_commas(params.stream())[..] {
+            [state, args] => (param_type(state), param_type(args)),
+            _ => (None, None),
+        };
+        let (state, args) = state.zip(args).ok_or(Error(
+            params.span(),
+            "expected parameters `(state: State, args: Args)`",
+        ))?;
+
+        let returns = match (iter.next(), iter.next(), iter.next()) {
+            (
+                Some(TokenTree::Punct(dash)),
+                Some(TokenTree::Punct(arrow)),
+                Some(TokenTree::Group(returns)),
+            ) if dash.as_char() == '-'
+                && arrow.as_char() == '>'
+                && returns.delimiter() == Delimiter::Parenthesis =>
+            {
+                returns
+            }
+            _ => {
+                return Err(Error(
+                    name.span(),
+                    "expected return type `-> (State, Output)`",
+                ))
+            }
+        };
+        let (new, output) = match &split_commas(returns.stream())[..] {
+            [new, output] => (
+                new.iter().cloned().collect(),
+                output.iter().cloned().collect(),
+            ),
+            _ => {
+                return Err(Error(
+                    returns.span(),
+                    "expected return type `-> (State, Output)`",
+                ))
+            }
+        };
+
+        match iter.next() {
+            Some(TokenTree::Group(body))
+                if body.delimiter() == Delimiter::Brace => {}
+            Some(tt) => {
+                return Err(Error(
+                    tt.span(),
+                    "where clauses aren't supported by `#[pure_fn]`",
+                ))
+            }
+            None => return Err(Error(name.span(), "expected function body")),
+        }
+
+        Ok(Self {
+            docs,
+            vis,
+            name,
+            state,
+            args,
+            new,
+            output,
+        })
+    }
+
+    fn expand(self) -> TokenStream {
+        let span = self.name.span();
+        let marker = Ident::new(&camel_case(&self.name.to_string()), span);
+        let ext = Ident::new(&format!("{}Ext", marker), span);
+        let mut tokens = TokenStream::new();
+
+        // Marker type implementing `PureFn`
+        let mut def = self.docs.clone();
+
+        def.extend(self.vis.clone());
+        def.extend(parse("struct"));
+        def.extend([TokenTree::Ident(marker.clone())]);
+        def.extend(parse("="));
+        def.extend([TokenTree::Ident(self.name.clone())]);
+        def.extend(parse("as fn"));
+        def.extend([group(
+            Delimiter::Parenthesis,
+            join(self.state.clone(), self.args.clone()),
+        )]);
+        def.extend(parse("->"));
+        def.extend([group(
+            Delimiter::Parenthesis,
+            join(self.new.clone(), self.output.clone()),
+        )]);
+        def.extend(parse(";"));
+        tokens.extend(parse("::pure_cell::impl_pure_fn!"));
+        tokens.extend([group(Delimiter::Brace, def)]);
+
+        // Extension trait
+        let doc =
+            format!("Adds [`{}()`] as a method on `PureCell`.", self.name,);
+        let mut method = self.docs.clone();
+
+        method.extend(self.signature());
+        method.extend(parse(";"));
+        let mut attr = parse("doc =");
+
+        attr.extend([TokenTree::Literal(Literal::string(&doc))]);
+        tokens.extend(parse("#"));
+        tokens.extend([group(Delimiter::Bracket, attr)]);
+        tokens.extend(self.vis.clone());
+        tokens.extend(parse("trait"));
+        tokens.extend([TokenTree::Ident(ext.clone())]);
+        tokens.extend([group(Delimiter::Brace, method)]);
+
+        let mut method = parse("#[inline]");
+        let mut body = parse("self.update::<");
+
+        body.extend([TokenTree::Ident(marker)]);
+        body.extend(parse(">(args)"));
+        method.extend(self.signature());
+        method.extend([group(Delimiter::Brace, body)]);
+        tokens.extend(parse("impl"));
+        tokens.extend([TokenTree::Ident(ext)]);
+        tokens.extend(parse("for ::pure_cell::PureCell<"));
+        tokens.extend(self.state);
+        tokens.extend(parse(">"));
+        tokens.extend([group(Delimiter::Brace, method)]);
+        tokens
+    }
+
+    /// Signature of the extension method.
+    fn signature(&self) -> TokenStream {
+        let mut signature = parse("fn");
+        let mut params = parse("&self, args:");
+
+        params.extend(self.args.clone());
+        signature.extend([TokenTree::Ident(self.name.clone())]);
+        signature.extend([group(Delimiter::Parenthesis, params)]);
+        signature.extend(parse("-> ::core::result::Result<"));
+        signature.extend(self.output.clone());
+        signature.extend(parse(", ::pure_cell::PoisonError<"));
+        signature.extend(self.args.clone());
+        signature.extend(parse(">>"));
+        signature
+    }
+}
+
+fn parse(code: &str) -> TokenStream {
+    code.parse().unwrap()
+}
+
+fn group(delimiter: Delimiter, stream: TokenStream) -> TokenTree {
+    Group::new(delimiter, stream).into()
+}
+
+fn spanned(mut tt: TokenTree, span: Span) -> TokenTree {
+    tt.set_span(span);
+    tt
+}
+
+fn join(first: TokenStream, second: TokenStream) -> TokenStream {
+    let mut stream = first;
+
+    stream.extend(parse(","));
+    stream.extend(second);
+    stream
+}
+
+fn expect_ident(tt: Option<TokenTree>, name: &str) -> Option<()> {
+    match tt {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == name => Some(()),
+        _ => None,
+    }
+}
+
+/// Splits a list on commas that aren't inside angle brackets.
+fn split_commas(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    let mut depth = 0usize;
+    let mut dash = false;
+
+    for tt in stream {
+        if let TokenTree::Punct(ref punct) = tt {
+            match punct.as_char() {
+                '<' => depth += 1,
+                '>' if !dash => depth = depth.saturating_sub(1),
+                ',' if depth == 0 => {
+                    parts.push(Vec::new());
+                    dash = false;
+                    continue;
+                }
+                _ => {}
+            }
+            dash = punct.as_char() == '-';
+        } else {
+            dash = false;
+        }
+        parts.last_mut().unwrap().push(tt);
+    }
+
+    if matches!(parts.last(), Some(last) if last.is_empty()) {
+        parts.pop();
+    }
+
+    parts
+}
+
+/// Returns the type of a `pattern: Type` parameter.
+fn param_type(param: &[TokenTree]) -> Option<TokenStream> {
+    let mut path = false;
+
+    for (i, tt) in param.iter().enumerate() {
+        match tt {
+            TokenTree::Punct(punct) if punct.as_char() == ':' => {
+                if punct.spacing() == Spacing::Alone && !path {
+                    return Some(param[i + 1..].iter().cloned().collect());
+                }
+                path = punct.spacing() == Spacing::Joint;
+            }
+            _ => path = false,
+        }
+    }
+
+    None
+}
+
+/// Converts a `snake_case` function name to `CamelCase`.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
@@ -0,0 +1,366 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, rc::Rc, sync::Arc};
+use core::mem::ManuallyDrop;
+
+#[cfg(feature = "const-mut-refs")]
+use crate::PureFnMut;
+#[cfg(feature = "std")]
+use crate::PureLocalKey;
+use crate::{
+    CriticalPureCell, CriticalSection, PoisonError, PureCell, PureElement,
+    PureFn, PureLazy, SyncPureCell, SyncPureLazy,
+};
+
+/// Cell types that [`pure_cell!()`](crate::pure_cell) and the other macros
+/// can update.
+///
+/// Implemented by this crate's cell types, and by references and smart
+/// pointers to them (`Box`, `Rc` and `Arc` require the `alloc` feature).
+/// Implementing it also provides [`update()`](PureAccess::update) and
+/// [`modify()`](PureAccess::modify) (and `update_in_place()` with the
+/// `const-mut-refs` feature).
+///
+#[cfg_attr(feature = "alloc", doc = "```rust")]
+#[cfg_attr(not(feature = "alloc"), doc = "```rust,ignore")]
+/// use std::rc::Rc;
+///
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// let shared = Rc::new(PureCell::new(1));
+/// let other = Rc::clone(&shared);
+/// pure_cell!(other, 2, |state: u32, amount: u32| {
+///     state += amount;
+/// })
+/// .unwrap();
+/// assert_eq!(shared.get_copy(), 3);
+/// ```
+///
+/// Wrapper types can opt in by delegating to a cell they contain:
+///
+/// ```rust
+/// use core::{cell::Cell, mem::ManuallyDrop};
+///
+/// use pure_cell::{PoisonError, PureAccess, PureCell, pure_cell};
+///
+/// /// Counts the updates made to a `PureCell`
+/// struct Counted<T> {
+///     cell: PureCell<T>,
+///     updates: Cell<u32>,
+/// }
+///
+/// unsafe impl<T> PureAccess for Counted<T> {
+///     type Value = T;
+///
+///     unsafe fn with<A, R, F>(
+///         &self,
+///         input: A,
+///         f: F,
+///     ) -> Result<R, PoisonError<A>>
+///     where
+///         F: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+///     {
+///         self.updates.set(self.updates.get() + 1);
+///         self.cell.with(input, f)
+///     }
+///
+///     unsafe fn with_ref<A, R, F>(
+///         &self,
+///         input: A,
+///         f: F,
+///     ) -> Result<R, PoisonError<A>>
+///     where
+///         F: FnOnce(&T, A) -> R,
+///     {
+///         self.cell.with_ref(input, f)
+///     }
+///
+///     fn addr(&self) -> *const () {
+///         self.cell.addr()
+///     }
+/// }
+///
+/// let counted = Counted {
+///     cell: PureCell::new(1),
+///     updates: Cell::new(0),
+/// };
+/// pure_cell!(counted, 2, |state: u32, amount: u32| {
+///     state += amount;
+/// })
+/// .unwrap();
+/// assert_eq!(counted.cell.get_copy(), 3);
+/// assert_eq!(counted.updates.get(), 1);
+///
+/// counted.modify(|state| *state *= 2).unwrap();
+/// assert_eq!(counted.cell.get_copy(), 6);
+/// assert_eq!(counted.updates.get(), 2);
+/// ```
+///
+/// # Safety
+/// Implementations must uphold the guarantees of [`PureCell::with()`] and
+/// [`PureCell::with_ref()`], given callers that follow their rules:
+///
+///  - The closure gets the only reference to a valid value (for `with()`), or
+///    no mutable reference exists while it runs (for `with_ref()`)
+///  - If the closure of `with()` panics, the value is never used or dropped
+///    again, since it may have been moved out
+///  - Poisoned values are never passed to the closure; the input is returned
+///    in a [`PoisonError`] instead
+///  - [`addr()`](PureAccess::addr) returns the same address for two values
+///    whenever their `with()` methods can access the same cell
+///  - Once `addr()` has returned, `with()` and `with_ref()` don't access any
+///    other cell, or run code that could (like a lazy initializer), except by
+///    calling the closure, since [`pure_cells!()`](crate::pure_cells) calls
+///    them from within other cells' updates
+///  - Accessing the cell from the closure of `with()` panics or deadlocks,
+///    since [`modify()`](PureAccess::modify) passes it code that isn't const
+pub unsafe trait PureAccess {
+    /// Type of the value in the cell
+    type Value: ?Sized;
+
+    /// See [`PureCell::with()`].
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with()`].
+    unsafe fn with<A, R, F>(&self, input: A, f: F) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&mut ManuallyDrop<Self::Value>, A) -> R;
+
+    /// See [`PureCell::with_ref()`].
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with_ref()`].
+    unsafe fn with_ref<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&Self::Value, A) -> R;
+
+    /// Returns the address of the cell that [`with()`](PureAccess::with)
+    /// accesses, which [`pure_cells!()`](crate::pure_cells) uses to reject
+    /// the same cell being passed twice.
+    fn addr(&self) -> *const ();
+
+    /// Update cell with the named pure transition `F`.
+    ///
+    /// See [`PureFn`] for an example.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `args` if the cell was poisoned by an
+    /// earlier panicking update.
+    fn update<F>(
+        &self,
+        args: F::Args,
+    ) -> Result<F::Output, PoisonError<F::Args>>
+    where
+        F: PureFn<Self::Value>,
+        Self::Value: Sized,
+    {
+        unsafe {
+            self.with(args, |state, args| {
+                let (new, output) = F::call(ManuallyDrop::take(state), args);
+                *state = ManuallyDrop::new(new);
+                output
+            })
+        }
+    }
+
+    /// Update cell in place with the named pure transition `F`.
+    ///
+    /// Requires the `const-mut-refs` feature.  See [`PureFnMut`] for an
+    /// example.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `args` if the cell was poisoned by an
+    /// earlier panicking update.
+    #[cfg(feature = "const-mut-refs")]
+    fn update_in_place<F>(
+        &self,
+        args: F::Args,
+    ) -> Result<F::Output, PoisonError<F::Args>>
+    where
+        F: PureFnMut<Self::Value>,
+    {
+        unsafe { self.with(args, |state, args| F::call(state, args)) }
+    }
+
+    /// Update cell with a closure that isn't limited to const code.
+    ///
+    /// See [`PureCell::modify()`].  Accessing the cell from within the
+    /// closure panics, or deadlocks for cells that spin until they're
+    /// unlocked, like [`SyncPureCell`].
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `f` if the cell was poisoned by an
+    /// earlier panicking update.
+    fn modify<R, F>(&self, f: F) -> Result<R, PoisonError<F>>
+    where
+        F: FnOnce(&mut Self::Value) -> R,
+    {
+        unsafe { self.with(f, |state, f| f(state)) }
+    }
+}
+
+/// Implements [`PureAccess`] by calling the type's own methods.
+macro_rules! impl_access {
+    ($([$($generics:tt)*] $type:ty => $value:ty;)*) => ($(
+        unsafe impl<$($generics)*> PureAccess for $type {
+            type Value = $value;
+
+            unsafe fn with<A, R, F>(
+                &self,
+                input: A,
+                f: F,
+            ) -> Result<R, PoisonError<A>>
+            where
+                F: FnOnce(&mut ManuallyDrop<$value>, A) -> R,
+            {
+                <$type>::with(self, input, f)
+            }
+
+            unsafe fn with_ref<A, R, F>(
+                &self,
+                input: A,
+                f: F,
+            ) -> Result<R, PoisonError<A>>
+            where
+                F: FnOnce(&$value, A) -> R,
+            {
+                <$type>::with_ref(self, input, f)
+            }
+
+            fn addr(&self) -> *const () {
+                <$type>::addr(self)
+            }
+        }
+    )*);
+}
+
+impl_access! {
+    [T: ?Sized] PureCell<T> => T;
+    [T] SyncPureCell<T> => T;
+    [T, C: CriticalSection] CriticalPureCell<T, C> => T;
+    [T] PureElement<'_, T> => T;
+}
+
+#[cfg(feature = "std")]
+impl_access! {
+    [T: 'static] PureLocalKey<T> => T;
+}
+
+unsafe impl<T, F: FnOnce() -> T> PureAccess for PureLazy<T, F> {
+    type Value = T;
+
+    unsafe fn with<A, R, G>(&self, input: A, f: G) -> Result<R, PoisonError<A>>
+    where
+        G: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+    {
+        PureLazy::force(self).with(input, f)
+    }
+
+    unsafe fn with_ref<A, R, G>(
+        &self,
+        input: A,
+        f: G,
+    ) -> Result<R, PoisonError<A>>
+    where
+        G: FnOnce(&T, A) -> R,
+    {
+        PureLazy::force(self).with_ref(input, f)
+    }
+
+    fn addr(&self) -> *const () {
+        PureLazy::force(self).addr()
+    }
+}
+
+unsafe impl<T, F: FnOnce() -> T> PureAccess for SyncPureLazy<T, F> {
+    type Value = T;
+
+    unsafe fn with<A, R, G>(&self, input: A, f: G) -> Result<R, PoisonError<A>>
+    where
+        G: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+    {
+        SyncPureLazy::force(self).with(input, f)
+    }
+
+    unsafe fn with_ref<A, R, G>(
+        &self,
+        input: A,
+        f: G,
+    ) -> Result<R, PoisonError<A>>
+    where
+        G: FnOnce(&T, A) -> R,
+    {
+        SyncPureLazy::force(self).with_ref(input, f)
+    }
+
+    fn addr(&self) -> *const () {
+        SyncPureLazy::force(self).addr()
+    }
+}
+
+/// Implements [`PureAccess`] for pointers, by calling the pointee's methods.
+macro_rules! impl_pointer_access {
+    ($($type:ty;)*) => ($(
+        unsafe impl<C: PureAccess + ?Sized> PureAccess for $type {
+            type Value = C::Value;
+
+            unsafe fn with<A, R, F>(
+                &self,
+                input: A,
+                f: F,
+            ) -> Result<R, PoisonError<A>>
+            where
+                F: FnOnce(&mut ManuallyDrop<Self::Value>, A) -> R,
+            {
+                (**self).with(input, f)
+            }
+
+            unsafe fn with_ref<A, R, F>(
+                &self,
+                input: A,
+                f: F,
+            ) -> Result<R, PoisonError<A>>
+            where
+                F: FnOnce(&Self::Value, A) -> R,
+            {
+                (**self).with_ref(input, f)
+            }
+
+            fn addr(&self) -> *const () {
+                (**self).addr()
+            }
+        }
+    )*);
+}
+
+impl_pointer_access! {
+    &C;
+}
+
+#[cfg(feature = "alloc")]
+impl_pointer_access! {
+    Box<C>;
+    Rc<C>;
+    Arc<C>;
+}
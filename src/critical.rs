@@ -0,0 +1,304 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    marker::PhantomData,
+    mem::ManuallyDrop,
+};
+
+use crate::{PoisonError, PureCell};
+
+/// A way to run code without interruption, for [`CriticalPureCell`].
+///
+/// On bare-metal targets this usually disables interrupts.  With the
+/// `critical-section` feature, `GlobalCriticalSection` implements it by
+/// calling `critical_section::with()`.
+///
+/// Calls may be nested (for example, when
+/// [`pure_cells!()`](crate::pure_cells) updates two cells), which should run
+/// `f` rather than deadlock.
+///
+/// ```rust
+/// use std::{
+///     cell::Cell,
+///     hint,
+///     sync::atomic::{AtomicBool, Ordering},
+///     thread,
+/// };
+///
+/// use pure_cell::{CriticalPureCell, CriticalSection, pure_cell};
+///
+/// /// Critical section for testing on an operating system, which stops other
+/// /// threads instead of interrupts.
+/// struct Global;
+///
+/// static LOCKED: AtomicBool = AtomicBool::new(false);
+///
+/// thread_local! {
+///     static HELD: Cell<bool> = Cell::new(false);
+/// }
+///
+/// unsafe impl CriticalSection for Global {
+///     fn with<R>(f: impl FnOnce() -> R) -> R {
+///         if HELD.with(Cell::get) {
+///             return f();
+///         }
+///         while LOCKED.swap(true, Ordering::Acquire) {
+///             hint::spin_loop();
+///         }
+///         HELD.with(|held| held.set(true));
+///         let output = f();
+///         HELD.with(|held| held.set(false));
+///         LOCKED.store(false, Ordering::Release);
+///         output
+///     }
+/// }
+///
+/// static COUNTER: CriticalPureCell<u32, Global> = CriticalPureCell::new(0);
+///
+/// let threads: Vec<_> = (0..4)
+///     .map(|_| {
+///         thread::spawn(|| {
+///             for _ in 0..100 {
+///                 pure_cell!(COUNTER, 1, |state: u32, amount: u32| {
+///                     state += amount;
+///                 })
+///                 .unwrap();
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// assert_eq!(COUNTER.get_copy(), 400);
+/// ```
+///
+/// # Safety
+/// While `f` runs, no other code that calls `Self::with()` may run, whether
+/// on another thread, in an interrupt handler or in a signal handler.
+///
+/// `Self::with()` must not access any cell itself, other than by calling `f`,
+/// since it may be called from within another cell's update (see
+/// [`PureAccess`](crate::PureAccess)).
+pub unsafe trait CriticalSection {
+    /// Runs `f` inside the critical section.
+    fn with<R>(f: impl FnOnce() -> R) -> R;
+}
+
+/// The critical section of the `critical-section` crate, which is provided
+/// by the target's HAL (or by `critical-section`'s `std` feature on operating
+/// systems).
+///
+/// Requires the `critical-section` feature.
+#[cfg(feature = "critical-section")]
+#[derive(Copy, Clone, Debug)]
+pub struct GlobalCriticalSection;
+
+#[cfg(feature = "critical-section")]
+unsafe impl CriticalSection for GlobalCriticalSection {
+    fn with<R>(f: impl FnOnce() -> R) -> R {
+        critical_section::with(|_| f())
+    }
+}
+
+/// A [`PureCell`] that can be placed in a `static` and shared with interrupt
+/// handlers, since every access runs inside the critical section `C`.
+///
+/// Unlike [`SyncPureCell`](crate::SyncPureCell), an access never spins, so
+/// it can't deadlock with the code an interrupt handler interrupted.  Closures
+/// passed to [`modify()`](crate::PureAccess::modify) run inside the critical
+/// section too, so keep them short.
+///
+#[cfg_attr(feature = "critical-section", doc = "```rust")]
+#[cfg_attr(not(feature = "critical-section"), doc = "```rust,ignore")]
+/// use std::thread;
+///
+/// use pure_cell::{CriticalPureCell, GlobalCriticalSection, pure_cells};
+///
+/// static TICKS: CriticalPureCell<u32, GlobalCriticalSection> =
+///     CriticalPureCell::new(0);
+/// static TOTAL: CriticalPureCell<u64, GlobalCriticalSection> =
+///     CriticalPureCell::new(0);
+///
+/// let threads: Vec<_> = (0..4)
+///     .map(|_| {
+///         thread::spawn(|| {
+///             for _ in 0..100 {
+///                 pure_cells!([TICKS, TOTAL], 5, |ticks: u32, total: u64, amount: u64| {
+///                     ticks += 1;
+///                     total += amount;
+///                 })
+///                 .unwrap();
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// assert_eq!(TICKS.get_copy(), 400);
+/// assert_eq!(TOTAL.get_copy(), 2000);
+/// ```
+pub struct CriticalPureCell<T, C> {
+    cell: PureCell<T>,
+    critical_section: PhantomData<C>,
+}
+
+unsafe impl<T: Send, C: CriticalSection> Sync for CriticalPureCell<T, C> {}
+
+impl<T, C> CriticalPureCell<T, C> {
+    /// Creates a new `CriticalPureCell` containing the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            cell: PureCell::new(value),
+            critical_section: PhantomData,
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.cell.get_mut()
+    }
+
+    /// Consumes the cell, returning the wrapped value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn into_inner(self) -> T {
+        self.cell.into_inner()
+    }
+}
+
+impl<T, C: CriticalSection> CriticalPureCell<T, C> {
+    /// Replaces the contained value with `value`, and returns the old value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn replace(&self, value: T) -> T {
+        C::with(|| self.cell.replace(value))
+    }
+
+    /// Sets the contained value, dropping the old value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn set(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    /// Takes the contained value, leaving `Default::default()` in its place.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+
+    /// Returns a copy of the contained value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn get_copy(&self) -> T
+    where
+        T: Copy,
+    {
+        C::with(|| self.cell.get_copy())
+    }
+
+    /// Returns `true` if an earlier update panicked, poisoning the cell.
+    pub fn is_poisoned(&self) -> bool {
+        C::with(|| self.cell.is_poisoned())
+    }
+
+    /// Stores `value` in the cell and clears the poisoned state.
+    ///
+    /// If the cell wasn't poisoned, the old value is dropped.
+    pub fn clear_poison(&self, value: T) {
+        C::with(|| self.cell.clear_poison(value))
+    }
+
+    /// See [`PureCell::with()`], which this calls inside the critical
+    /// section.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with()`].
+    pub unsafe fn with<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+    {
+        C::with(|| self.cell.with(input, f))
+    }
+
+    /// See [`PureCell::with_ref()`], which this calls inside the critical
+    /// section.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with_ref()`].
+    pub unsafe fn with_ref<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&T, A) -> R,
+    {
+        C::with(|| self.cell.with_ref(input, f))
+    }
+
+    /// Returns the address of the cell, for
+    /// [`PureAccess::addr()`](crate::PureAccess::addr).
+    pub(crate) fn addr(&self) -> *const () {
+        self.cell.addr()
+    }
+}
+
+impl<T: Debug, C: CriticalSection> Debug for CriticalPureCell<T, C> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        C::with(|| {
+            f.debug_struct("CriticalPureCell")
+                .field("cell", &self.cell)
+                .finish()
+        })
+    }
+}
+
+impl<T: Default, C> Default for CriticalPureCell<T, C> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T, C> From<T> for CriticalPureCell<T, C> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
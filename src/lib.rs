@@ -13,16 +13,26 @@
 //!
 //! ## Advantages
 //! - Simple, with no cell keys
-//! - Works better with thread local global state.
+//! - Works better with thread local global state (see `pure_thread_local!()`,
+//!   which requires the `std` feature)
 //!
 //! ## Disadvantages
 //! - Const closures/fn pointers don't exist (yet), so this crate depends on
 //!   macro magic to sort-of-polyfill them
-//! - Might not always optimize well (TODO)
+//! - Updates move the state out of the cell and back, which optimizes to an
+//!   in-place update for small state types, but copies large ones twice (see
+//!   `benches/update.rs`); [`pure_cell_field!()`] and [`PureElement`] can
+//!   update part of a large state in place
 //!
-//! Once const contexts support mutable references, this crate will be able to
-//! remove the second disadvantage.  Additionally, once const function pointers
-//! stabilize, this crate will be able to remove the first disadvantage.
+//! Const contexts support mutable references since Rust 1.83, so the opt-in
+//! `const-mut-refs` feature removes the second disadvantage by updating the
+//! state in place (see [`pure_cell!()`]).  It's a feature to keep supporting
+//! older compilers.  Once const function pointers stabilize, this crate will
+//! be able to remove the first disadvantage.
+//!
+//! Updates that can't be written as const code can use the safe
+//! [`PureCell::modify()`], which trades the compile-time guarantee for a
+//! runtime borrow flag.
 //!
 //! # Getting Started
 //! ```rust
@@ -31,8 +41,9 @@
 //! let mut cell = PureCell::new(15);
 //! pure_cell!(cell, (), |state: u32, _args: ()| {
 //!     state += 1;
-//! });
-//! let got = cell.get();
+//! })
+//! .unwrap();
+//! let got = cell.get_mut();
 //! assert_eq!(*got, 16);
 //! ```
 //!
@@ -44,7 +55,8 @@
 //! let state = pure_cell!(cell, amount, |state: u32, amount: u32| -> u32 {
 //!     state += amount;
 //!     state
-//! });
+//! })
+//! .unwrap();
 //! assert_eq!(state, 17);
 //! ```
 
@@ -70,11 +82,161 @@
     variant_size_differences
 )]
 
-use core::{cell::UnsafeCell, mem::ManuallyDrop};
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod access;
+mod critical;
+#[cfg(feature = "std")]
+mod local;
+mod macros;
+mod once;
+mod poison;
+mod pure_fn;
+mod slice;
+mod sync;
+
+use core::{
+    cell::{Cell, UnsafeCell},
+    cmp::Ordering,
+    fmt::{self, Debug, Formatter},
+    hash::{Hash, Hasher},
+    mem::{self, ManuallyDrop},
+    ptr,
+};
+
+#[cfg(feature = "critical-section")]
+pub use self::critical::GlobalCriticalSection;
+#[cfg(feature = "const-mut-refs")]
+pub use self::pure_fn::PureFnMut;
+pub use self::{
+    access::PureAccess,
+    critical::{CriticalPureCell, CriticalSection},
+    once::{PureLazy, PureOnceCell, SyncPureLazy},
+    poison::PoisonError,
+    pure_fn::PureFn,
+    slice::{Elements, PureElement},
+    sync::SyncPureCell,
+};
+#[cfg(feature = "macros")]
+pub use pure_cell_macros::pure_fn;
+
+#[cfg(feature = "std")]
+pub use self::local::PureLocalKey;
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use std::thread_local as __thread_local;
+
+/// Status of the value inside a [`PureCell`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum State {
+    /// The cell holds a valid value.
+    Ready,
+    /// The value is borrowed while running code that could access the cell.
+    Borrowed,
+    /// The value is being updated by [`PureCell::with()`].
+    Updating,
+    /// An update panicked, so the value may have been moved out.
+    Poisoned,
+}
+
+/// Poisons the cell when dropped, which only happens while unwinding.
+struct PoisonGuard<'a>(&'a Cell<State>);
+
+impl Drop for PoisonGuard<'_> {
+    fn drop(&mut self) {
+        self.0.set(State::Poisoned);
+    }
+}
+
+/// Ends a borrow of the cell when dropped.
+struct BorrowGuard<'a>(&'a Cell<State>);
+
+impl Drop for BorrowGuard<'_> {
+    fn drop(&mut self) {
+        self.0.set(State::Ready);
+    }
+}
 
 /// A cell type that provides interior mutability via "pure" functions.
-#[derive(Debug)]
-pub struct PureCell<T> {
+///
+/// Cloning, comparing, hashing and formatting a `PureCell` look at the
+/// contained value, so `PureCell`s can be fields of types that derive those
+/// traits.  Formatting a borrowed or poisoned cell shows `<borrowed>` or
+/// `<poisoned>` in place of the value, while the other traits panic.
+///
+/// The value may be unsized, so a `&PureCell<[T; N]>` coerces to a
+/// `&PureCell<[T]>`, whose elements can be updated one at a time through
+/// [`PureElement`]s.
+///
+/// ```rust
+/// use std::{
+///     cmp::Ordering,
+///     collections::hash_map::DefaultHasher,
+///     hash::{Hash, Hasher},
+/// };
+///
+/// use pure_cell::PureCell;
+///
+/// #[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// struct Counters {
+///     hits: PureCell<u32>,
+///     misses: PureCell<u32>,
+/// }
+///
+/// fn hash(value: &impl Hash) -> u64 {
+///     let mut hasher = DefaultHasher::new();
+///     value.hash(&mut hasher);
+///     hasher.finish()
+/// }
+///
+/// let counters = Counters::default();
+/// counters.hits.set(3);
+/// let copy = counters.clone();
+/// assert_eq!(copy, counters);
+/// assert_eq!(format!("{:?}", copy.hits), "PureCell { value: 3 }");
+///
+/// copy.misses.set(1);
+/// assert_ne!(copy, counters);
+/// assert_eq!(copy.cmp(&counters), Ordering::Greater);
+/// assert_eq!(counters.partial_cmp(&copy), Some(Ordering::Less));
+/// assert_eq!(copy.hits.cmp(&copy.misses), Ordering::Greater);
+/// assert_eq!(hash(&copy.hits), hash(&3u32));
+/// assert_eq!(hash(&copy.hits), hash(&counters.hits));
+/// assert_ne!(hash(&copy), hash(&counters));
+/// ```
+///
+/// ```rust
+/// use std::panic::{self, AssertUnwindSafe};
+///
+/// use pure_cell::PureCell;
+///
+/// fn panic_message(f: impl FnOnce()) -> &'static str {
+///     let error = panic::catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+///     error.downcast_ref::<&str>().unwrap()
+/// }
+///
+/// let cell = PureCell::new(1);
+///
+/// // The cell is borrowed while `modify()` runs its closure
+/// let debug = cell.modify(|_| format!("{:?}", cell)).unwrap();
+/// assert_eq!(debug, "PureCell { value: <borrowed> }");
+/// let message = panic_message(|| {
+///     let _ = cell.modify(|_| cell.clone());
+/// });
+/// assert_eq!(message, "PureCell already borrowed");
+///
+/// // That panic poisoned the cell
+/// assert_eq!(format!("{:?}", cell), "PureCell { value: <poisoned> }");
+/// let message = panic_message(|| {
+///     let _ = cell.clone();
+/// });
+/// assert_eq!(message, "PureCell poisoned by a panicking update");
+/// ```
+pub struct PureCell<T: ?Sized> {
+    state: Cell<State>,
     value: UnsafeCell<ManuallyDrop<T>>,
 }
 
@@ -82,69 +244,620 @@ impl<T> PureCell<T> {
     /// Creates a new `PureCell` containing the given value.
     pub const fn new(value: T) -> Self {
         Self {
+            state: Cell::new(State::Ready),
             value: UnsafeCell::new(ManuallyDrop::new(value)),
         }
     }
 
+    /// Consumes the cell, returning the wrapped value.
+    ///
+    /// ```rust
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(String::from("hello"));
+    /// assert_eq!(cell.into_inner(), "hello");
+    /// ```
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    ///
+    /// ```rust,should_panic
+    /// use std::panic::{self, AssertUnwindSafe};
+    ///
+    /// use pure_cell::{PureCell, pure_cell};
+    ///
+    /// let cell = PureCell::new(15);
+    /// let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///     pure_cell!(cell, 0, |state: u32, divisor: u32| {
+    ///         state /= divisor;
+    ///     })
+    /// }));
+    /// assert!(cell.is_poisoned());
+    /// // Panics with "PureCell poisoned by a panicking update"
+    /// let _ = cell.into_inner();
+    /// ```
+    pub fn into_inner(self) -> T {
+        self.assert_ready();
+
+        let this = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::take(&mut *this.value.get()) }
+    }
+
+    /// Replaces the contained value with `value`, and returns the old value.
+    ///
+    /// ```rust
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(vec![1, 2]);
+    /// assert_eq!(cell.replace(vec![3]), [1, 2]);
+    /// assert_eq!(cell.into_inner(), [3]);
+    /// ```
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    ///
+    /// ```rust,should_panic
+    /// use std::panic::{self, AssertUnwindSafe};
+    ///
+    /// use pure_cell::{PureCell, pure_cell};
+    ///
+    /// let cell = PureCell::new(15);
+    /// let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///     pure_cell!(cell, 0, |state: u32, divisor: u32| {
+    ///         state /= divisor;
+    ///     })
+    /// }));
+    /// assert!(cell.is_poisoned());
+    /// // Panics with "PureCell poisoned by a panicking update"
+    /// let _ = cell.replace(0);
+    /// ```
+    pub fn replace(&self, value: T) -> T {
+        self.assert_ready();
+
+        unsafe { mem::replace(&mut **self.value.get(), value) }
+    }
+
+    /// Sets the contained value, dropping the old value.
+    ///
+    /// ```rust
+    /// use std::rc::Rc;
+    ///
+    /// use pure_cell::PureCell;
+    ///
+    /// let old = Rc::new(1);
+    /// let cell = PureCell::new(Rc::clone(&old));
+    /// cell.set(Rc::new(2));
+    /// assert_eq!(Rc::strong_count(&old), 1);
+    /// assert_eq!(*cell.into_inner(), 2);
+    /// ```
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn set(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    /// Takes the contained value, leaving `Default::default()` in its place.
+    ///
+    /// ```rust
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(Some(5));
+    /// assert_eq!(cell.take(), Some(5));
+    /// assert_eq!(cell.take(), None);
+    /// ```
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+
+    /// Returns a copy of the contained value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    ///
+    /// ```rust,should_panic
+    /// use std::panic::{self, AssertUnwindSafe};
+    ///
+    /// use pure_cell::{PureCell, pure_cell};
+    ///
+    /// let cell = PureCell::new(15);
+    /// let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///     pure_cell!(cell, 0, |state: u32, divisor: u32| {
+    ///         state /= divisor;
+    ///     })
+    /// }));
+    /// assert!(cell.is_poisoned());
+    /// // Panics with "PureCell poisoned by a panicking update"
+    /// let _ = cell.get_copy();
+    /// ```
+    pub fn get_copy(&self) -> T
+    where
+        T: Copy,
+    {
+        self.assert_ready();
+
+        unsafe { **self.value.get() }
+    }
+
+    /// Swaps the values of two cells.
+    ///
+    /// ```rust
+    /// use pure_cell::PureCell;
+    ///
+    /// let a = PureCell::new(1);
+    /// let b = PureCell::new(2);
+    /// a.swap(&b);
+    /// assert_eq!(a.get_copy(), 2);
+    /// assert_eq!(b.get_copy(), 1);
+    /// ```
+    ///
+    /// # Panics
+    /// If either cell was poisoned by a panicking update.
+    pub fn swap(&self, other: &Self) {
+        if ptr::eq(self, other) {
+            return;
+        }
+
+        self.assert_ready();
+        other.assert_ready();
+
+        unsafe { ptr::swap(self.value.get(), other.value.get()) }
+    }
+
+    /// Stores `value` in the cell and clears the poisoned state.
+    ///
+    /// If the cell wasn't poisoned, the old value is dropped.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed by one of the cell's trait
+    /// implementations (for example, from within the value's `Clone`
+    /// implementation).
+    ///
+    /// ```rust
+    /// use std::panic::{self, AssertUnwindSafe};
+    ///
+    /// use pure_cell::{PureCell, pure_cell};
+    ///
+    /// let cell = PureCell::new(15);
+    /// let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+    ///     pure_cell!(cell, 0, |state: u32, divisor: u32| {
+    ///         state /= divisor;
+    ///     })
+    /// }));
+    /// assert!(cell.is_poisoned());
+    ///
+    /// let error = pure_cell!(cell, 2, |state: u32, amount: u32| {
+    ///     state += amount;
+    /// })
+    /// .unwrap_err();
+    /// assert_eq!(error.into_inner(), 2);
+    ///
+    /// cell.clear_poison(0);
+    /// assert!(!cell.is_poisoned());
+    /// let state = pure_cell!(cell, 2, |state: u32, amount: u32| -> u32 {
+    ///     state += amount;
+    ///     state
+    /// });
+    /// assert_eq!(state.unwrap(), 2);
+    /// ```
+    pub fn clear_poison(&self, value: T) {
+        self.assert_unborrowed();
+
+        let old = unsafe {
+            mem::replace(&mut *self.value.get(), ManuallyDrop::new(value))
+        };
+
+        if self.state.replace(State::Ready) == State::Ready {
+            drop(ManuallyDrop::into_inner(old));
+        }
+    }
+
+    /// Update cell with the named pure transition `F`.
+    ///
+    /// See [`PureFn`] for an example.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `args` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed by one of the cell's trait
+    /// implementations (for example, from within the value's `Clone`
+    /// implementation).
+    pub fn update<F: PureFn<T>>(
+        &self,
+        args: F::Args,
+    ) -> Result<F::Output, PoisonError<F::Args>> {
+        PureAccess::update::<F>(self, args)
+    }
+}
+
+impl<T: ?Sized> PureCell<T> {
     /// Returns a mutable reference to the underlying data.
+    ///
+    /// Same as [`Self::get_mut()`].
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
     pub fn get(&mut self) -> &mut T {
+        self.get_mut()
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.assert_ready();
         self.value.get_mut()
     }
 
-    /// Update cell.
+    /// Returns `true` if an earlier update panicked, poisoning the cell.
+    pub fn is_poisoned(&self) -> bool {
+        self.state.get() == State::Poisoned
+    }
+
+    /// Update cell in place with the named pure transition `F`.
+    ///
+    /// Requires the `const-mut-refs` feature.  See [`PureFnMut`] for an
+    /// example.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `args` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed by one of the cell's trait
+    /// implementations (for example, from within the value's `Clone`
+    /// implementation).
+    #[cfg(feature = "const-mut-refs")]
+    pub fn update_in_place<F: PureFnMut<T>>(
+        &self,
+        args: F::Args,
+    ) -> Result<F::Output, PoisonError<F::Args>> {
+        PureAccess::update_in_place::<F>(self, args)
+    }
+
+    /// Update cell with a closure that isn't limited to const code.
+    ///
+    /// Unlike [`pure_cell!()`], the closure can call trait methods, allocate
+    /// and use iterators.  This is safe because the cell is marked as borrowed
+    /// (in all builds) while the closure runs:
+    ///
+    ///  - Any access to the cell from within the closure, including from the
+    ///    value's own trait implementations, panics instead of aliasing the
+    ///    mutable reference
+    ///  - The closure can't hold the reference across an `.await`, since it
+    ///    isn't async
+    ///  - `PureCell` isn't `Sync`, so no other thread can access the cell
+    ///  - If the closure panics, the cell is poisoned, so the value is never
+    ///    observed again (it is leaked rather than dropped)
+    ///
+    /// The cost compared to [`pure_cell!()`] is setting and clearing the
+    /// borrow flag.
+    ///
+    /// ```rust
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(vec![3, 1, 2]);
+    /// let total = cell
+    ///     .modify(|list| {
+    ///         list.sort();
+    ///         list.push(4);
+    ///         list.iter().sum::<i32>()
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(total, 10);
+    /// assert_eq!(cell.into_inner(), [1, 2, 3, 4]);
+    /// ```
+    ///
+    /// The closure can't return a future that holds on to the value either:
+    ///
+    /// ```rust,compile_fail
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(1);
+    /// let future = cell.modify(|state| async move { *state += 1 }).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `f` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Panics
+    /// If called while the cell is borrowed, which includes from within the
+    /// closure of another `modify()` on this cell.
+    ///
+    /// ```rust,should_panic
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(1);
+    /// // Panics with "PureCell already borrowed"
+    /// let _ = cell.modify(|_| cell.get_copy());
+    /// ```
+    ///
+    /// ```rust,should_panic
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(1);
+    /// // Panics with "PureCell already borrowed"
+    /// let _ = cell.modify(|_| cell.modify(|state| *state += 1));
+    /// ```
+    pub fn modify<R, F>(&self, f: F) -> Result<R, PoisonError<F>>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.modify_with(f, |value, f| f(value))
+    }
+
+    /// Update cell, passing `input` along to the closure.
+    ///
+    /// If the closure panics, the cell is poisoned and its value is never
+    /// dropped (it may have been moved out by the closure).
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed by one of the cell's trait
+    /// implementations (for example, from within the value's `Clone`
+    /// implementation).
+    ///
+    /// Also if called from within the closure of another `with()` on this
+    /// cell.
+    ///
+    /// # Safety
+    /// Sound to use so long as you follow these rules in the closure:
+    ///
+    ///  - Must not yield to other code (usually async)
+    ///  - Must not access this cell (recursively calling `Self::with()`,
+    ///    `Self::clear_poison()`, etc.)
+    ///  - Must leave a valid value in the cell when returning normally
+    ///
+    /// [`pure_cell!()`] upholds these rules at compile time, including in async
+    /// code (see its docs).
+    ///
+    /// Accessing the cell from the closure, including from a `Drop`
+    /// implementation that runs while the closure unwinds, panics rather than
+    /// causing undefined behavior.
+    ///
+    /// ```rust
+    /// use std::panic::{self, AssertUnwindSafe};
+    ///
+    /// use pure_cell::PureCell;
+    ///
+    /// let cell = PureCell::new(1);
+    /// // Breaks the rules above
+    /// let error = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+    ///     cell.with((), |_, ()| cell.set(2))
+    /// }))
+    /// .unwrap_err();
+    /// assert_eq!(
+    ///     error.downcast_ref::<&str>(),
+    ///     Some(&"PureCell accessed from within its own update"),
+    /// );
+    /// assert!(cell.is_poisoned());
+    /// ```
+    pub unsafe fn with<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+    {
+        self.assert_unborrowed();
+
+        if self.is_poisoned() {
+            return Err(PoisonError::new(input));
+        }
+
+        let guard = PoisonGuard(&self.state);
+        self.state.set(State::Updating);
+        let output = f(&mut *self.value.get(), input);
+        mem::forget(guard);
+        self.state.set(State::Ready);
+        Ok(output)
+    }
+
+    /// Read cell, passing `input` along to the closure.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed by one of the cell's trait
+    /// implementations (for example, from within the value's `Clone`
+    /// implementation).
+    ///
+    /// Also if called from within the closure of another `with()` or
+    /// `with_ref()` on this cell.
     ///
     /// # Safety
     /// Sound to use so long as you follow these rules in the closure:
     ///
     ///  - Must not yield to other code (usually async)
-    ///  - Must not recursively call `Self::with()`
-    pub unsafe fn with<R, F>(&self, f: F) -> R
+    ///  - Must not mutably access this cell (calling `Self::with()`,
+    ///    `Self::clear_poison()`, etc.)
+    pub unsafe fn with_ref<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
     where
-        F: FnOnce(&mut ManuallyDrop<T>) -> R,
+        F: FnOnce(&T, A) -> R,
     {
-        f(&mut *self.value.get())
+        self.assert_unborrowed();
+
+        if self.is_poisoned() {
+            return Err(PoisonError::new(input));
+        }
+
+        self.state.set(State::Borrowed);
+        let _guard = BorrowGuard(&self.state);
+        Ok(f(&*self.value.get(), input))
     }
-}
 
-impl<T> Drop for PureCell<T> {
-    fn drop(&mut self) {
-        unsafe {
-            let _ = ManuallyDrop::take(&mut *self.value.get());
+    /// Like [`Self::modify()`], passing `input` along to the closure.
+    fn modify_with<A, R>(
+        &self,
+        input: A,
+        f: impl FnOnce(&mut T, A) -> R,
+    ) -> Result<R, PoisonError<A>> {
+        self.assert_unborrowed();
+
+        if self.is_poisoned() {
+            return Err(PoisonError::new(input));
         }
+
+        let guard = PoisonGuard(&self.state);
+        self.state.set(State::Borrowed);
+        let output = f(unsafe { &mut **self.value.get() }, input);
+        mem::forget(guard);
+        self.state.set(State::Ready);
+        Ok(output)
+    }
+
+    /// Calls `f` with a reference to the value.  Any access to the cell from
+    /// within `f` panics.
+    fn borrow<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.assert_ready();
+        self.state.set(State::Borrowed);
+
+        let _guard = BorrowGuard(&self.state);
+        f(unsafe { &*self.value.get() })
     }
-}
 
-/// Main safe mechanism to mutate [`PureCell`] via a `const` expression.
-#[macro_export]
-macro_rules! pure_cell {
-    (
-        $pure_cell:expr,
-        $input:expr,
-        |$state:ident: $ty:ty, $args:ident: $argty:ty| -> $ret:ty $block:block
-    ) => ({
-        #[inline(always)]
-        const fn const_fn(mut $state: $ty, mut $args: $argty) -> ($ty, $ret) {
-            let (output, state) = ($block, $state);
-            (state, output)
+    /// Like [`Self::borrow()`], for two cells which may be the same cell.
+    fn borrow_pair<R>(&self, other: &Self, f: impl FnOnce(&T, &T) -> R) -> R {
+        if ptr::eq(self, other) {
+            return self.borrow(|value| f(value, value));
         }
-        fn wrapper_fn(
-            state: &mut core::mem::ManuallyDrop<$ty>,
-            input: $argty,
-        ) -> $ret {
-            unsafe {
-                let (new, out) = const_fn(
-                    core::mem::ManuallyDrop::take(state),
-                    input,
-                );
-                *state = core::mem::ManuallyDrop::new(new);
-                out
+
+        self.borrow(|value| other.borrow(|other| f(value, other)))
+    }
+
+    fn assert_unborrowed(&self) {
+        match self.state.get() {
+            State::Borrowed => panic!("PureCell already borrowed"),
+            State::Updating => {
+                panic!("PureCell accessed from within its own update")
             }
+            State::Ready | State::Poisoned => {}
+        }
+    }
+
+    fn assert_ready(&self) {
+        self.assert_unborrowed();
+
+        if self.is_poisoned() {
+            panic!("PureCell poisoned by a panicking update");
         }
-        unsafe {
-            $pure_cell.with(move |state| wrapper_fn(state, $input))
+    }
+
+    /// Returns the address of the cell, for [`PureAccess::addr()`].
+    fn addr(&self) -> *const () {
+        let cell: *const Self = self;
+
+        cell.cast()
+    }
+}
+
+impl<T: ?Sized> Drop for PureCell<T> {
+    fn drop(&mut self) {
+        if self.is_poisoned() {
+            return;
+        }
+
+        unsafe { ManuallyDrop::drop(self.value.get_mut()) }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for PureCell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("PureCell");
+
+        match self.state.get() {
+            State::Ready => self.borrow(|value| f.field("value", &value)),
+            State::Borrowed => f.field("value", &format_args!("<borrowed>")),
+            State::Updating => f.field("value", &format_args!("<updating>")),
+            State::Poisoned => f.field("value", &format_args!("<poisoned>")),
+        };
+        f.finish()
+    }
+}
+
+impl<T: Clone> Clone for PureCell<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.borrow(T::clone))
+    }
+}
+
+impl<T: ?Sized + PartialEq> PartialEq for PureCell<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.borrow_pair(other, T::eq)
+    }
+}
+
+impl<T: ?Sized + Eq> Eq for PureCell<T> {}
+
+impl<T: ?Sized + PartialOrd> PartialOrd for PureCell<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.borrow_pair(other, T::partial_cmp)
+    }
+}
+
+impl<T: ?Sized + Ord> Ord for PureCell<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.borrow_pair(other, T::cmp)
+    }
+}
+
+impl<T: ?Sized + Hash> Hash for PureCell<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.borrow(|value| value.hash(state));
+    }
+}
+
+impl<T: Default> Default for PureCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for PureCell<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Panics if two of the addresses are the same, for [`pure_cells!()`].
+#[doc(hidden)]
+pub fn __assert_distinct(cells: &[*const ()]) {
+    for (i, cell) in cells.iter().enumerate() {
+        if cells[..i].contains(cell) {
+            panic!("the same cell was passed to `pure_cells!()` twice");
         }
-    });
-    ($pure_cell:expr, $input:expr, |$state:ident: $ty:ty, $args:ident: $argty:ty| $block:block) => (
-        $crate::pure_cell!($pure_cell, $input, |$state: $ty, $args: $argty| -> () $block)
-    );
+    }
+}
+
+/// The new state and output of a [`try_pure_cell!()`] update, named so that
+/// the type error from an early `return Ok(..)` explains that it needs `&mut`
+/// state.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct __ReturnOkRequiresMutState<S, O>(pub S, pub O);
+
+/// Copies `value`, so [`try_pure_cell!()`] requires a `Copy` state.
+#[doc(hidden)]
+pub fn __copy<T: Copy>(value: &T) -> T {
+    *value
 }
@@ -0,0 +1,127 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    mem::ManuallyDrop,
+};
+use std::thread::LocalKey;
+
+use crate::{PoisonError, PureCell};
+
+/// A thread local [`PureCell`], declared with
+/// [`pure_thread_local!()`](crate::pure_thread_local).
+///
+/// Each method accesses the current thread's cell, and panics if that cell
+/// has already been destroyed (like [`LocalKey::with()`]).
+pub struct PureLocalKey<T: 'static> {
+    key: &'static LocalKey<PureCell<T>>,
+}
+
+impl<T: 'static> PureLocalKey<T> {
+    #[doc(hidden)]
+    pub const fn new(key: &'static LocalKey<PureCell<T>>) -> Self {
+        Self { key }
+    }
+
+    /// Calls `f` with a reference to the current thread's cell.
+    pub fn with_cell<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&PureCell<T>) -> R,
+    {
+        self.key.with(f)
+    }
+
+    /// See [`PureCell::is_poisoned()`].
+    pub fn is_poisoned(&self) -> bool {
+        self.with_cell(PureCell::is_poisoned)
+    }
+
+    /// See [`PureCell::clear_poison()`].
+    pub fn clear_poison(&self, value: T) {
+        self.with_cell(|cell| cell.clear_poison(value))
+    }
+
+    /// See [`PureCell::replace()`].
+    pub fn replace(&self, value: T) -> T {
+        self.with_cell(|cell| cell.replace(value))
+    }
+
+    /// See [`PureCell::set()`].
+    pub fn set(&self, value: T) {
+        self.with_cell(|cell| cell.set(value))
+    }
+
+    /// See [`PureCell::take()`].
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.with_cell(PureCell::take)
+    }
+
+    /// See [`PureCell::get_copy()`].
+    pub fn get_copy(&self) -> T
+    where
+        T: Copy,
+    {
+        self.with_cell(PureCell::get_copy)
+    }
+
+    /// See [`PureCell::with()`].
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with()`].
+    pub unsafe fn with<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+    {
+        self.with_cell(|cell| cell.with(input, f))
+    }
+
+    /// See [`PureCell::with_ref()`].
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with_ref()`].
+    pub unsafe fn with_ref<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&T, A) -> R,
+    {
+        self.with_cell(|cell| cell.with_ref(input, f))
+    }
+
+    /// Returns the address of this thread's cell, for
+    /// [`PureAccess::addr()`](crate::PureAccess::addr).
+    pub(crate) fn addr(&self) -> *const () {
+        self.with_cell(PureCell::addr)
+    }
+}
+
+impl<T: 'static> Debug for PureLocalKey<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PureLocalKey").finish_non_exhaustive()
+    }
+}
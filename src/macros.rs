@@ -0,0 +1,1234 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+/// Main safe mechanism to mutate [`PureCell`](crate::PureCell) via a `const`
+/// expression.
+///
+/// Evaluates to a `Result` of the const expression's output.
+///
+/// The state parameter of the closure may be an identifier, `_`, or a tuple,
+/// tuple struct or struct (using shorthand fields) pattern of identifiers,
+/// which is put back together as the new state after the const expression.
+/// The input parameter may be an identifier, `_`, or a tuple pattern of those.
+/// All bindings are mutable.
+///
+/// Generic parameters used by the closure's types must be declared before the
+/// closure, since the const expression can't see those of the surrounding
+/// item.  Trait bounds on them require Rust 1.61 or later.
+///
+/// ```rust
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// struct Slot<T> {
+///     cell: PureCell<Option<T>>,
+/// }
+///
+/// impl<T> Slot<T> {
+///     fn take(&self) -> Option<T> {
+///         pure_cell!(self.cell, (), <T> |state: Option<T>, _: ()| -> Option<T> {
+///             let taken = state;
+///             state = None;
+///             taken
+///         })
+///         .unwrap()
+///     }
+///
+///     fn put(&self, value: T) -> Option<T> {
+///         pure_cell!(self.cell, value, <T> |state: Option<T>, value: T| -> Option<T> {
+///             let old = state;
+///             state = Some(value);
+///             old
+///         })
+///         .unwrap()
+///     }
+/// }
+///
+/// let slot = Slot { cell: PureCell::new(Some("hello")) };
+/// assert_eq!(slot.take(), Some("hello"));
+/// assert_eq!(slot.take(), None);
+/// assert_eq!(slot.put("world"), None);
+/// assert_eq!(slot.put("again"), Some("world"));
+/// ```
+///
+/// ```rust
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// type Offset = (i32, (i32, i32));
+///
+/// let cell = PureCell::new(Point { x: 1, y: 2 });
+/// let offset = (3, (4, 5));
+/// let sum = pure_cell!(cell, offset, |Point { x, y }: Point, (dx, (dy, _)): Offset| -> i32 {
+///     x += dx;
+///     y += dy;
+///     x + y
+/// });
+/// assert_eq!(sum.unwrap(), 10);
+///
+/// let cell = PureCell::new((1, 2));
+/// pure_cell!(cell, (), |(a, b): (u32, u32), _: ()| {
+///     let old = a;
+///     a = b;
+///     b = old;
+/// })
+/// .unwrap();
+/// assert_eq!(cell.get_copy(), (2, 1));
+/// ```
+///
+/// # Arguments
+/// The cell and input expressions are evaluated before, and outside of, the
+/// `unsafe` block inside the macro, so they can't perform unsafe operations:
+///
+/// ```rust,compile_fail
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// let cell = PureCell::new(1);
+/// let raw: *const PureCell<u32> = &cell;
+/// pure_cell!(*raw, (), |state: u32, _: ()| {
+///     state += 1;
+/// })
+/// .unwrap();
+/// ```
+///
+/// ```rust,compile_fail
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// let cell = PureCell::new(1);
+/// let amount = 2;
+/// let raw: *const u32 = &amount;
+/// pure_cell!(cell, *raw, |state: u32, amount: u32| {
+///     state += amount;
+/// })
+/// .unwrap();
+/// ```
+///
+/// The cell must implement [`PureAccess`](crate::PureAccess), rather than
+/// just have a `with()` method:
+///
+/// ```rust,compile_fail
+/// use core::mem::ManuallyDrop;
+///
+/// use pure_cell::{PoisonError, pure_cell};
+///
+/// struct Fake(u32);
+///
+/// impl Fake {
+///     unsafe fn with<A, R, F>(&self, input: A, f: F) -> Result<R, PoisonError<A>>
+///     where
+///         F: FnOnce(&mut ManuallyDrop<u32>, A) -> R,
+///     {
+///         unimplemented!()
+///     }
+/// }
+///
+/// pure_cell!(Fake(1), (), |state: u32, _: ()| {
+///     state += 1;
+/// })
+/// .unwrap();
+/// ```
+///
+/// # In-place updates
+/// With the `const-mut-refs` feature (Rust 1.83 or later), the state may be
+/// declared as `name: &mut Type`.  The const expression then updates the state
+/// in place, rather than moving it out of the cell and back, so large states
+/// aren't copied.  This also works with
+/// [`pure_cell_field!()`](crate::pure_cell_field), and for unsized states like
+/// slices.
+///
+#[cfg_attr(feature = "const-mut-refs", doc = "```rust")]
+#[cfg_attr(not(feature = "const-mut-refs"), doc = "```rust,compile_fail")]
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// let cell = PureCell::new([0u8; 4096]);
+/// let sum = pure_cell!(cell, 7, |buffer: &mut [u8; 4096], byte: u8| -> u8 {
+///     buffer[0] = 1;
+///     buffer[1] = byte;
+///     buffer[0] + buffer[1]
+/// });
+/// assert_eq!(sum.unwrap(), 8);
+///
+/// let slice: &PureCell<[u8]> = &cell;
+/// pure_cell!(slice, (), |buffer: &mut [u8], _: ()| {
+///     buffer[4095] = 2;
+/// })
+/// .unwrap();
+/// assert_eq!(cell.into_inner()[4095], 2);
+/// ```
+///
+/// # Async
+/// `pure_cell!()` is safe to use from async code.  The state can't be held
+/// across an `.await`, because the const expression runs to completion before
+/// the macro returns, and it can't contain `.await` itself:
+///
+/// ```rust,compile_fail
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// async fn tick() {}
+///
+/// async fn update(cell: &PureCell<u32>) {
+///     pure_cell!(cell, (), |state: u32, _: ()| {
+///         tick().await;
+///         state += 1;
+///     })
+///     .unwrap();
+/// }
+/// ```
+///
+/// Futures may update the same cell between their own `.await`s, even when
+/// they are interleaved on one thread:
+///
+/// ```rust
+/// use std::{
+///     future::Future,
+///     pin::Pin,
+///     ptr,
+///     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
+/// };
+///
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+///
+/// fn clone(_: *const ()) -> RawWaker {
+///     RawWaker::new(ptr::null(), &VTABLE)
+/// }
+///
+/// fn noop(_: *const ()) {}
+///
+/// /// Polls two futures on this thread, alternating until both finish
+/// fn block_on_both(a: impl Future<Output = ()>, b: impl Future<Output = ()>) {
+///     let waker = unsafe { Waker::from_raw(clone(ptr::null())) };
+///     let mut cx = Context::from_waker(&waker);
+///     let (mut a, mut b) = (Box::pin(a), Box::pin(b));
+///     let (mut a_done, mut b_done) = (false, false);
+///
+///     while !(a_done && b_done) {
+///         a_done = a_done || a.as_mut().poll(&mut cx).is_ready();
+///         b_done = b_done || b.as_mut().poll(&mut cx).is_ready();
+///     }
+/// }
+///
+/// /// Returns pending once, yielding to the other future
+/// struct Yield(bool);
+///
+/// impl Future for Yield {
+///     type Output = ();
+///
+///     fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+///         if self.0 {
+///             return Poll::Ready(());
+///         }
+///         self.0 = true;
+///         Poll::Pending
+///     }
+/// }
+///
+/// /// Appends `id` as a digit of the cell's value, twice
+/// async fn record(cell: &PureCell<u32>, id: u32) {
+///     for _ in 0..2 {
+///         pure_cell!(cell, id, |state: u32, id: u32| {
+///             state = state * 10 + id;
+///         })
+///         .unwrap();
+///         Yield(false).await;
+///     }
+/// }
+///
+/// let cell = PureCell::new(0);
+/// block_on_both(record(&cell, 1), record(&cell, 2));
+/// assert_eq!(cell.get_copy(), 1212);
+/// ```
+///
+/// # Errors
+/// If the const expression panics, the cell is poisoned instead of being left
+/// with a moved-out value, and all later updates return
+/// [`PoisonError`](crate::PoisonError) holding their unconsumed input.
+///
+/// ```rust
+/// use core::sync::atomic::{AtomicUsize, Ordering};
+/// use std::panic::{self, AssertUnwindSafe};
+///
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// static DROPS: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct Tracked(u32);
+///
+/// impl Drop for Tracked {
+///     fn drop(&mut self) {
+///         DROPS.fetch_add(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// let cell = PureCell::new(Tracked(15));
+/// let divisor = 0;
+/// let result = panic::catch_unwind(AssertUnwindSafe(|| {
+///     pure_cell!(cell, divisor, |state: Tracked, divisor: u32| {
+///         state.0 /= divisor;
+///     })
+/// }));
+/// assert!(result.is_err());
+/// assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+///
+/// let result = pure_cell!(cell, (), |state: Tracked, _args: ()| {
+///     state.0 += 1;
+/// });
+/// assert!(result.is_err());
+/// assert!(cell.is_poisoned());
+///
+/// drop(cell);
+/// assert_eq!(DROPS.load(Ordering::SeqCst), 1);
+/// ```
+///
+/// The cell is marked as being updated while the const expression runs, in
+/// all builds.  So if the state's `Drop` implementation accesses the cell while
+/// the const expression unwinds, that access panics rather than seeing the
+/// moved-out state:
+///
+/// ```rust
+/// use std::panic::{self, AssertUnwindSafe};
+///
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// struct Guard(Option<Box<u64>>, u32);
+///
+/// thread_local! {
+///     static CELL: PureCell<Guard> = PureCell::new(Guard(Some(Box::new(1)), 15));
+/// }
+///
+/// impl Drop for Guard {
+///     fn drop(&mut self) {
+///         if self.0.is_none() {
+///             return;
+///         }
+///         CELL.with(|cell| {
+///             let replaced = panic::catch_unwind(AssertUnwindSafe(|| {
+///                 cell.replace(Guard(None, 0))
+///             }));
+///             assert!(replaced.is_err());
+///         });
+///     }
+/// }
+///
+/// let result = panic::catch_unwind(|| {
+///     CELL.with(|cell| {
+///         pure_cell!(cell, 0, |state: Guard, divisor: u32| {
+///             state.1 /= divisor;
+///         })
+///     })
+/// });
+/// assert!(result.is_err());
+/// assert!(CELL.with(PureCell::is_poisoned));
+/// ```
+#[macro_export]
+macro_rules! pure_cell {
+    (
+        $pure_cell:expr,
+        $input:expr,
+        $(<$($gen:ident $(: $bound:ident $(+ $bounds:ident)*)?),* $(,)?>)?
+        |$($closure:tt)*
+    ) => (
+        $crate::__pure_cell!(
+            @state [
+                [$pure_cell] [$input] [$($($gen $(: $bound $(+ $bounds)*)?),*)?]
+                []
+            ]
+            $($closure)*
+        )
+    );
+}
+
+/// Parses the closure passed to [`pure_cell!()`], one part at a time.
+///
+/// `$call` is `[[cell] [input] [generics] [projection]]`, passed through
+/// unchanged.  The projection is empty, `Type.field` for
+/// [`pure_cell_field!()`],
+/// or `try` for [`try_pure_cell!()`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pure_cell {
+    // State patterns, as `[pattern] [expression to put it back together]`
+    (@state $call:tt _: $ty:ty, $($rest:tt)*) => (
+        $crate::__pure_cell!(@args $call [mut state] [state] $ty, $($rest)*)
+    );
+    (@state $call:tt $state:ident: &mut $ty:ty, $($rest:tt)*) => (
+        $crate::__pure_cell!(@args $call [&mut $state] [] $ty, $($rest)*)
+    );
+    (@state $call:tt $state:ident: $ty:ty, $($rest:tt)*) => (
+        $crate::__pure_cell!(@args $call [mut $state] [$state] $ty, $($rest)*)
+    );
+    (
+        @state $call:tt
+        $($path:ident)::+ { $($field:ident),* $(,)? }: $ty:ty, $($rest:tt)*
+    ) => (
+        $crate::__pure_cell!(
+            @args $call
+            [$($path)::+ { $(mut $field,)* }] [$($path)::+ { $($field,)* }]
+            $ty, $($rest)*
+        )
+    );
+    (
+        @state $call:tt
+        $($path:ident)::+ ($($field:ident),* $(,)?): $ty:ty, $($rest:tt)*
+    ) => (
+        $crate::__pure_cell!(
+            @args $call
+            [$($path)::+ ($(mut $field,)*)] [$($path)::+ ($($field,)*)]
+            $ty, $($rest)*
+        )
+    );
+    (@state $call:tt ($($field:ident),* $(,)?): $ty:ty, $($rest:tt)*) => (
+        $crate::__pure_cell!(
+            @args $call [($(mut $field,)*)] [($($field,)*)] $ty, $($rest)*
+        )
+    );
+    // Input pattern
+    (
+        @args $call:tt $spat:tt $sexpr:tt $ty:ty,
+        $args:tt: $argty:ty| $($rest:tt)*
+    ) => (
+        $crate::__pure_cell!(
+            @ret $call $spat $sexpr $ty, $args, $argty, $($rest)*
+        )
+    );
+    // Return type
+    (
+        @ret [[$pure_cell:expr] [$input:expr] [$($gen:tt)*] [try]]
+        [&mut $state:ident] [] $ty:ty, $args:tt, $argty:ty,
+        -> Result<$ok:ty, $err:ty> $block:block
+    ) => ($crate::__const_mut_refs! {{
+        #[inline(always)]
+        const fn const_fn<$($gen)*>(
+            $state: &mut $ty,
+            args: $argty,
+        ) -> core::result::Result<$ok, $err> {
+            #[allow(unused_mut)]
+            let $crate::__pure_cell_pat!($args) = args;
+            $block
+        }
+        fn wrapper_fn<$($gen)*>(
+            state: &mut core::mem::ManuallyDrop<$ty>,
+            input: $argty,
+        ) -> core::result::Result<$ok, $err> {
+            // Update a copy, so the state is left untouched on `Err`
+            let mut new = $crate::__copy(&**state);
+            let output = const_fn(&mut new, input);
+            if output.is_ok() {
+                **state = new;
+            }
+            output
+        }
+        let cell = &$pure_cell;
+        let input = $input;
+        unsafe { $crate::PureAccess::with(cell, input, wrapper_fn) }
+    }});
+    (
+        @ret [[$pure_cell:expr] [$input:expr] [$($gen:tt)*] [try]]
+        [$($spat:tt)*] [$($sexpr:tt)*] $ty:ty, $args:tt, $argty:ty,
+        -> Result<$ok:ty, $err:ty> $block:block
+    ) => ({
+        #[inline(always)]
+        const fn const_fn<$($gen)*>(
+            state: $ty,
+            args: $argty,
+        ) -> core::result::Result<
+            $crate::__ReturnOkRequiresMutState<$ty, $ok>,
+            $err,
+        > {
+            #[allow(unused_mut)]
+            let $($spat)* = state;
+            #[allow(unused_mut)]
+            let $crate::__pure_cell_pat!($args) = args;
+            let output: core::result::Result<$ok, $err> = $block;
+            match output {
+                core::result::Result::Ok(output) => core::result::Result::Ok(
+                    $crate::__ReturnOkRequiresMutState($($sexpr)*, output),
+                ),
+                core::result::Result::Err(error) => {
+                    core::result::Result::Err(error)
+                }
+            }
+        }
+        fn wrapper_fn<$($gen)*>(
+            state: &mut core::mem::ManuallyDrop<$ty>,
+            input: $argty,
+        ) -> core::result::Result<$ok, $err> {
+            match const_fn($crate::__copy(&**state), input) {
+                core::result::Result::Ok(
+                    $crate::__ReturnOkRequiresMutState(new, output),
+                ) => {
+                    **state = new;
+                    core::result::Result::Ok(output)
+                }
+                core::result::Result::Err(error) => {
+                    core::result::Result::Err(error)
+                }
+            }
+        }
+        let cell = &$pure_cell;
+        let input = $input;
+        unsafe { $crate::PureAccess::with(cell, input, wrapper_fn) }
+    });
+    (
+        @ret $call:tt $spat:tt $sexpr:tt $ty:ty, $args:tt, $argty:ty,
+        -> $ret:ty $block:block
+    ) => (
+        $crate::__pure_cell!(
+            @update $call $spat $sexpr $ty, $args, $argty, $ret, $block
+        )
+    );
+    (
+        @ret $call:tt $spat:tt $sexpr:tt $ty:ty, $args:tt, $argty:ty,
+        $block:block
+    ) => (
+        $crate::__pure_cell!(
+            @update $call $spat $sexpr $ty, $args, $argty, (), $block
+        )
+    );
+    (
+        @update [[$pure_cell:expr] [$input:expr] [$($gen:tt)*] [$($proj:tt)*]]
+        [&mut $state:ident] []
+        $ty:ty, $args:tt, $argty:ty, $ret:ty, $block:block
+    ) => ($crate::__const_mut_refs! {{
+        #[inline(always)]
+        const fn const_fn<$($gen)*>($state: &mut $ty, args: $argty) -> $ret {
+            #[allow(unused_mut)]
+            let $crate::__pure_cell_pat!($args) = args;
+            $block
+        }
+        let cell = &$pure_cell;
+        let input = $input;
+        unsafe {
+            $crate::PureAccess::with(cell, input, |state, input: $argty| -> $ret {
+                const_fn($crate::__pure_cell_field!(state $($proj)*), input)
+            })
+        }
+    }});
+    (
+        @update [[$pure_cell:expr] [$input:expr] [$($gen:tt)*] []]
+        [$($spat:tt)*] [$($sexpr:tt)*]
+        $ty:ty, $args:tt, $argty:ty, $ret:ty, $block:block
+    ) => ({
+        $crate::__pure_cell!(
+            @const_fn [$($gen)*] [$($spat)*] [$($sexpr)*]
+            $ty, $args, $argty, $ret, $block
+        );
+        fn wrapper_fn<$($gen)*>(
+            state: &mut core::mem::ManuallyDrop<$ty>,
+            input: $argty,
+        ) -> $ret {
+            unsafe {
+                let (new, out) = const_fn(
+                    core::mem::ManuallyDrop::take(state),
+                    input,
+                );
+                *state = core::mem::ManuallyDrop::new(new);
+                out
+            }
+        }
+        let cell = &$pure_cell;
+        let input = $input;
+        unsafe { $crate::PureAccess::with(cell, input, wrapper_fn) }
+    });
+    (
+        @update [[$pure_cell:expr] [$input:expr] [$($gen:tt)*] [$($proj:tt)+]]
+        [$($spat:tt)*] [$($sexpr:tt)*]
+        $ty:ty, $args:tt, $argty:ty, $ret:ty, $block:block
+    ) => ({
+        $crate::__pure_cell!(
+            @const_fn [$($gen)*] [$($spat)*] [$($sexpr)*]
+            $ty, $args, $argty, $ret, $block
+        );
+        let cell = &$pure_cell;
+        let input = $input;
+        unsafe {
+            $crate::PureAccess::with(cell, input, |state, input: $argty| -> $ret {
+                let field: *mut $ty = $crate::__pure_cell_field!(state $($proj)+);
+                let (new, out) = const_fn(core::ptr::read(field), input);
+                core::ptr::write(field, new);
+                out
+            })
+        }
+    });
+    (
+        @const_fn [$($gen:tt)*] [$($spat:tt)*] [$($sexpr:tt)*]
+        $ty:ty, $args:tt, $argty:ty, $ret:ty, $block:block
+    ) => (
+        #[inline(always)]
+        const fn const_fn<$($gen)*>(state: $ty, args: $argty) -> ($ty, $ret) {
+            #[allow(unused_mut)]
+            let $($spat)* = state;
+            #[allow(unused_mut)]
+            let $crate::__pure_cell_pat!($args) = args;
+            let output = $block;
+            ($($sexpr)*, output)
+        }
+    );
+}
+
+/// Like [`pure_cell!()`], but the const expression returns a `Result`, and
+/// the new state is only stored when it's `Ok`.
+///
+/// The return type must be written as `Result<R, E>`.  On `Err`, the cell
+/// keeps its original state, so the state type must be `Copy`.  Evaluates to
+/// a `Result` of the const expression's `Result`.
+///
+/// An early `return Err(error)` works inside the const expression.  An early
+/// `return Ok(output)` needs the state declared as `&mut` (see below), since
+/// the new state can't be put back together from its bindings after
+/// returning.  The `?` operator doesn't work, since it can't be used in
+/// `const fn`s on stable Rust.
+///
+/// ```rust
+/// use pure_cell::{PureCell, try_pure_cell};
+///
+/// #[derive(Copy, Clone, Debug, PartialEq)]
+/// struct Account {
+///     balance: u32,
+///     withdrawals: u32,
+/// }
+///
+/// let cell = PureCell::new(Account { balance: 10, withdrawals: 0 });
+/// let withdraw = |amount: u32| {
+///     try_pure_cell!(
+///         cell,
+///         amount,
+///         |Account { balance, withdrawals }: Account, amount: u32|
+///             -> Result<u32, &'static str>
+///         {
+///             withdrawals += 1;
+///             if amount > balance {
+///                 return Err("insufficient funds");
+///             }
+///             balance -= amount;
+///             Ok(balance)
+///         }
+///     )
+///     .unwrap()
+/// };
+///
+/// assert_eq!(withdraw(4), Ok(6));
+/// assert_eq!(withdraw(7), Err("insufficient funds"));
+/// assert_eq!(cell.get_copy(), Account { balance: 6, withdrawals: 1 });
+/// ```
+///
+/// With by-value state, an early `return Ok(..)` is a type error that expects
+/// `__ReturnOkRequiresMutState`:
+///
+/// ```rust,compile_fail
+/// use pure_cell::{PureCell, try_pure_cell};
+///
+/// let cell = PureCell::new(0u32);
+/// let result = try_pure_cell!(cell, 5, |state: u32, limit: u32| -> Result<u32, ()> {
+///     if state == limit {
+///         return Ok(state);
+///     }
+///     state += 1;
+///     Ok(state)
+/// });
+/// ```
+///
+/// With the `const-mut-refs` feature (Rust 1.83 or later), the state may be
+/// declared as `name: &mut Type`.  The const expression then updates a copy of
+/// the state, which is only stored when it returns `Ok`, and may return early
+/// with either variant.
+///
+#[cfg_attr(feature = "const-mut-refs", doc = "```rust")]
+#[cfg_attr(not(feature = "const-mut-refs"), doc = "```rust,compile_fail")]
+/// use pure_cell::{PureCell, try_pure_cell};
+///
+/// let cell = PureCell::new([0u32; 4]);
+/// let push = |value: u32| {
+///     try_pure_cell!(cell, value, |list: &mut [u32; 4], value: u32| -> Result<usize, u32> {
+///         let mut i = 0;
+///         while i < list.len() {
+///             if list[i] == value {
+///                 return Ok(i);
+///             }
+///             if list[i] == 0 {
+///                 list[i] = value;
+///                 return Ok(i);
+///             }
+///             i += 1;
+///         }
+///         list[0] = 0;
+///         Err(value)
+///     })
+///     .unwrap()
+/// };
+///
+/// assert_eq!(push(7), Ok(0));
+/// assert_eq!(push(8), Ok(1));
+/// assert_eq!(push(7), Ok(0));
+/// assert_eq!(push(9), Ok(2));
+/// assert_eq!(push(3), Ok(3));
+/// assert_eq!(push(5), Err(5));
+/// assert_eq!(cell.get_copy(), [7, 8, 9, 3]);
+/// ```
+///
+/// ```rust,compile_fail
+/// use pure_cell::{PureCell, try_pure_cell};
+///
+/// let cell = PureCell::new(String::new());
+/// let result = try_pure_cell!(cell, (), |state: String, _: ()| -> Result<(), ()> {
+///     Ok(())
+/// });
+/// ```
+#[macro_export]
+macro_rules! try_pure_cell {
+    (
+        $pure_cell:expr,
+        $input:expr,
+        $(<$($gen:ident $(: $bound:ident $(+ $bounds:ident)*)?),* $(,)?>)?
+        |$($closure:tt)*
+    ) => (
+        $crate::__pure_cell!(
+            @state [
+                [$pure_cell] [$input] [$($($gen $(: $bound $(+ $bounds)*)?),*)?]
+                [try]
+            ]
+            $($closure)*
+        )
+    );
+}
+
+/// Like [`pure_cell!()`], but updates several cells in one const expression.
+///
+/// The first argument is a list of cells, and the closure takes one state for
+/// each cell, in order, before the input.  Every cell's state is taken out,
+/// passed to the const expression, and written back together, so an
+/// invariant spanning the cells holds whenever none of them are being
+/// updated.  The states must be identifiers, rather than patterns.
+///
+/// The cells are accessed one inside another, in the order they're passed.
+/// Cells that lock, like [`SyncPureCell`](crate::SyncPureCell), stay locked
+/// until the update finishes, so always pass the same cells in the same order.
+/// Otherwise two threads running `pure_cells!([a, b], ..)` and
+/// `pure_cells!([b, a], ..)` can each lock one cell and spin forever waiting
+/// for the other.
+///
+/// ```rust
+/// use pure_cell::{PureCell, pure_cells};
+///
+/// struct Bank {
+///     balance: PureCell<u64>,
+///     ledger_len: PureCell<usize>,
+/// }
+///
+/// impl Bank {
+///     fn deposit(&self, amount: u64) -> usize {
+///         pure_cells!(
+///             [self.balance, self.ledger_len],
+///             amount,
+///             |balance: u64, len: usize, amount: u64| -> usize {
+///                 balance += amount;
+///                 len += 1;
+///                 len
+///             }
+///         )
+///         .unwrap()
+///     }
+/// }
+///
+/// let bank = Bank {
+///     balance: PureCell::new(0),
+///     ledger_len: PureCell::new(0),
+/// };
+/// assert_eq!(bank.deposit(5), 1);
+/// assert_eq!(bank.deposit(7), 2);
+/// assert_eq!(bank.balance.get_copy(), 12);
+/// ```
+///
+/// # Panics
+/// If the same cell is passed more than once, before any of the cells are
+/// updated.  Cells are shared, so this can't be checked at compile time; it
+/// compares [`PureAccess::addr()`](crate::PureAccess::addr) instead, which
+/// also catches two pointers to the same cell and two elements of the same
+/// slice.
+///
+/// ```rust,should_panic
+/// use pure_cell::{PureCell, pure_cells};
+///
+/// let cell = PureCell::new(1);
+/// let alias = &cell;
+/// let _ = pure_cells!([cell, alias], (), |a: u32, b: u32, _: ()| {
+///     a += 1;
+///     b += 1;
+/// });
+/// ```
+///
+/// # Errors
+/// If any of the cells is poisoned, none of them are updated, and the input
+/// is handed back in a [`PoisonError`](crate::PoisonError).  If the const
+/// expression panics, all of the cells are poisoned.
+#[macro_export]
+macro_rules! pure_cells {
+    (
+        [$($pure_cell:expr),+ $(,)?],
+        $input:expr,
+        $(<$($gen:ident $(: $bound:ident $(+ $bounds:ident)*)?),* $(,)?>)?
+        |$($closure:tt)*
+    ) => (
+        $crate::__pure_cells!(
+            @state [
+                [$($pure_cell),+] [$input]
+                [$($($gen $(: $bound $(+ $bounds)*)?),*)?]
+            ]
+            [$($pure_cell),+] []
+            $($closure)*
+        )
+    );
+}
+
+/// Parses the closure passed to [`pure_cells!()`], taking one state for each
+/// cell, then expands to one [`PureAccess::with()`] call nested in another
+/// for each cell.
+///
+/// Each recursion declares its own `cell`, `state` or `new` variable, which
+/// macro hygiene keeps distinct, collecting them into a list.
+///
+/// [`PureAccess::with()`]: crate::PureAccess::with
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pure_cells {
+    // States, as `[name type]`, until there's one for each cell
+    (
+        @state $call:tt [] $states:tt
+        $args:tt: $argty:ty| -> $ret:ty $block:block
+    ) => (
+        $crate::__pure_cells!(@update $call $states $args, $argty, $ret, $block)
+    );
+    (@state $call:tt [] $states:tt $args:tt: $argty:ty| $block:block) => (
+        $crate::__pure_cells!(@update $call $states $args, $argty, (), $block)
+    );
+    (
+        @state $call:tt [$pure_cell:expr $(, $rest:expr)*] [$($states:tt)*]
+        $state:ident: $ty:ty, $($closure:tt)*
+    ) => (
+        $crate::__pure_cells!(
+            @state $call [$($rest),*] [$($states)* [$state $ty]] $($closure)*
+        )
+    );
+    (
+        @update [[$($pure_cell:expr),+] [$input:expr] [$($gen:tt)*]]
+        [$([$state:ident $ty:ty])+]
+        $args:tt, $argty:ty, $ret:ty, $block:block
+    ) => ({
+        #[inline(always)]
+        #[allow(unused_mut)]
+        const fn const_fn<$($gen)*>(
+            $(mut $state: $ty,)+
+            args: $argty,
+        ) -> ($($ty,)+ $ret) {
+            let $crate::__pure_cell_pat!($args) = args;
+            let output = $block;
+            ($($state,)+ output)
+        }
+        $crate::__pure_cells!(@bind [] [$($pure_cell),+] $input, $argty)
+    });
+    // Bind each cell, then the input, outside of `unsafe`.  Every `addr()` is
+    // called before any `with()`, which lets lazily initialized cells run
+    // their initializers outside of the other cells' updates.
+    (@bind [$($cell:ident)+] [] $input:expr, $argty:ty) => ({
+        let input = $input;
+        $crate::__assert_distinct(&[$($crate::PureAccess::addr($cell)),+]);
+        unsafe { $crate::__pure_cells!(@with [$($cell)+] [] input, $argty) }
+    });
+    (
+        @bind [$($cells:ident)*] [$pure_cell:expr $(, $rest:expr)*]
+        $input:expr, $argty:ty
+    ) => ({
+        let cell = &$pure_cell;
+        $crate::__pure_cells!(
+            @bind [$($cells)* cell] [$($rest),*] $input, $argty
+        )
+    });
+    // Access each cell, flattening the nested `Result`s
+    (@with [] [$($state:ident)+] $input:ident, $argty:ty) => (
+        $crate::__pure_cells!(@take [] [$($state)+] $input)
+    );
+    (
+        @with [$cell:ident $($cells:ident)*] [$($states:ident)*]
+        $input:ident, $argty:ty
+    ) => (
+        match $crate::PureAccess::with($cell, $input, |state, input: $argty| {
+            $crate::__pure_cells!(
+                @with [$($cells)*] [$($states)* state] input, $argty
+            )
+        }) {
+            core::result::Result::Ok(result) => result,
+            core::result::Result::Err(error) => core::result::Result::Err(error),
+        }
+    );
+    // Name each new state, then take the states out and write them back
+    (@take [$($state:ident => $new:ident)+] [] $input:ident) => ({
+        let ($($new,)+ output) = const_fn(
+            $(core::mem::ManuallyDrop::take($state),)+
+            $input,
+        );
+        $(*$state = core::mem::ManuallyDrop::new($new);)+
+        core::result::Result::Ok(output)
+    });
+    (
+        @take [$($done:tt)*] [$state:ident $($states:ident)*]
+        $input:ident
+    ) => (
+        $crate::__pure_cells!(
+            @take [$($done)* $state => new] [$($states)*] $input
+        )
+    );
+}
+
+/// Like [`pure_cell!()`], but updates a single field of the cell's value.
+///
+/// Only the field is moved out of the cell and back, rather than the whole
+/// value, which matters for wide state types.  The second argument is the
+/// type of the cell's value followed by `.field`, where the type is a struct
+/// path (without generics) or a tuple type, and the field is a field name or
+/// tuple index.  Fields of fields aren't supported.
+///
+/// If the const expression panics, the whole cell is poisoned.
+///
+/// ```rust
+/// use pure_cell::{PureCell, pure_cell_field};
+///
+/// struct Stats {
+///     counter: u32,
+///     history: [u32; 1024],
+/// }
+///
+/// struct Server {
+///     stats: PureCell<Stats>,
+/// }
+///
+/// impl Server {
+///     fn count(&self, amount: u32) -> u32 {
+///         pure_cell_field!(self.stats, Stats.counter, amount, |c: u32, amt: u32| -> u32 {
+///             c += amt;
+///             c
+///         })
+///         .unwrap()
+///     }
+/// }
+///
+/// let server = Server {
+///     stats: PureCell::new(Stats { counter: 0, history: [0; 1024] }),
+/// };
+/// assert_eq!(server.count(2), 2);
+/// assert_eq!(server.count(3), 5);
+///
+/// let pair = PureCell::new((1, 'a'));
+/// pure_cell_field!(pair, (u32, char).1, (), |letter: char, _: ()| {
+///     letter = 'b';
+/// })
+/// .unwrap();
+/// assert_eq!(pair.get_copy(), (1, 'b'));
+/// ```
+///
+/// The field must belong to the named type itself.  Fields reached through
+/// `Deref` are rejected, since a `DerefMut` implementation could access the
+/// cell during the update:
+///
+/// ```rust,compile_fail
+/// use core::ops::{Deref, DerefMut};
+///
+/// use pure_cell::{PureCell, pure_cell_field};
+///
+/// struct Inner {
+///     counter: u32,
+/// }
+///
+/// struct Outer(Inner);
+///
+/// impl Deref for Outer {
+///     type Target = Inner;
+///
+///     fn deref(&self) -> &Inner {
+///         &self.0
+///     }
+/// }
+///
+/// impl DerefMut for Outer {
+///     fn deref_mut(&mut self) -> &mut Inner {
+///         &mut self.0
+///     }
+/// }
+///
+/// let cell = PureCell::new(Outer(Inner { counter: 0 }));
+/// pure_cell_field!(cell, Inner.counter, (), |c: u32, _: ()| {
+///     c += 1;
+/// })
+/// .unwrap();
+/// ```
+#[macro_export]
+macro_rules! pure_cell_field {
+    (
+        $pure_cell:expr, ($($elem:ty),+ $(,)?) . $field:tt,
+        $input:expr,
+        $(<$($gen:ident $(: $bound:ident $(+ $bounds:ident)*)?),* $(,)?>)?
+        |$($closure:tt)*
+    ) => (
+        $crate::__pure_cell!(
+            @state [
+                [$pure_cell] [$input]
+                [$($($gen $(: $bound $(+ $bounds)*)?),*)?]
+                [($($elem),+) . $field]
+            ]
+            $($closure)*
+        )
+    );
+    (
+        $pure_cell:expr, $($path:ident)::+ . $field:tt,
+        $input:expr,
+        $(<$($gen:ident $(: $bound:ident $(+ $bounds:ident)*)?),* $(,)?>)?
+        |$($closure:tt)*
+    ) => (
+        $crate::__pure_cell!(
+            @state [
+                [$pure_cell] [$input]
+                [$($($gen $(: $bound $(+ $bounds)*)?),*)?]
+                [$($path)::+ . $field]
+            ]
+            $($closure)*
+        )
+    );
+}
+
+/// Projects a `&mut ManuallyDrop<T>` to a `&mut` of the whole value, or of
+/// the field named by [`pure_cell_field!()`].
+///
+/// Struct fields are reached with a struct pattern, which never dereferences
+/// through a user `DerefMut` implementation, unlike field access.  Tuples
+/// can't implement `Deref`, so their fields are accessed directly.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pure_cell_field {
+    ($state:ident) => (&mut **$state);
+    ($state:ident ($($elem:ty),+) . $field:tt) => ({
+        let value: &mut ($($elem,)+) = &mut **$state;
+        &mut value.$field
+    });
+    ($state:ident $($path:ident)::+ . $field:tt) => ({
+        let $($path)::+ { $field: field, .. } = &mut **$state;
+        field
+    });
+}
+
+/// Implements [`PureFn`](crate::PureFn) for new unit structs, each calling a
+/// `const fn` with the signature `const fn(State, Args) -> (State, Output)`.
+///
+/// With the `const-mut-refs` feature, structs declared with the signature
+/// `fn(&mut State, Args) -> Output` implement
+/// `PureFnMut` instead.
+///
+/// See [`PureFn`](crate::PureFn) for an example.  Functions that aren't
+/// `const fn` are rejected:
+///
+/// ```rust,compile_fail
+/// use pure_cell::impl_pure_fn;
+///
+/// fn increment(count: u32, amount: u32) -> (u32, u32) {
+///     (count + amount, count + amount)
+/// }
+///
+/// impl_pure_fn! {
+///     struct Increment = increment as fn(u32, u32) -> (u32, u32);
+/// }
+/// ```
+#[macro_export]
+macro_rules! impl_pure_fn {
+    () => ();
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident = $fn:path as fn(&mut $state:ty, $args:ty)
+            -> $output:ty;
+        $($rest:tt)*
+    ) => (
+        $crate::__const_mut_refs! {
+            $(#[$attr])*
+            #[derive(Copy, Clone, Debug)]
+            $vis struct $name;
+
+            unsafe impl $crate::PureFnMut<$state> for $name {
+                type Args = $args;
+                type Output = $output;
+
+                #[inline(always)]
+                fn call(state: &mut $state, args: $args) -> $output {
+                    const fn pure(state: &mut $state, args: $args) -> $output {
+                        $fn(state, args)
+                    }
+                    pure(state, args)
+                }
+            }
+        }
+
+        $crate::impl_pure_fn!($($rest)*);
+    );
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident = $fn:path as fn($state:ty, $args:ty)
+            -> ($new:ty, $output:ty);
+        $($rest:tt)*
+    ) => (
+        $(#[$attr])*
+        #[derive(Copy, Clone, Debug)]
+        $vis struct $name;
+
+        unsafe impl $crate::PureFn<$state> for $name {
+            type Args = $args;
+            type Output = $output;
+
+            #[inline(always)]
+            fn call(state: $state, args: $args) -> ($state, $output) {
+                const fn pure(state: $state, args: $args) -> ($new, $output) {
+                    $fn(state, args)
+                }
+                pure(state, args)
+            }
+        }
+
+        $crate::impl_pure_fn!($($rest)*);
+    );
+}
+
+/// Expands to its input if the `const-mut-refs` feature is enabled.
+#[cfg(feature = "const-mut-refs")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __const_mut_refs {
+    ($($tokens:tt)*) => ($($tokens)*);
+}
+
+/// Expands to its input if the `const-mut-refs` feature is enabled.
+#[cfg(not(feature = "const-mut-refs"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __const_mut_refs {
+    ($($tokens:tt)*) => {
+        compile_error! {
+            "`&mut` state requires the `const-mut-refs` feature of `pure_cell`"
+        }
+    };
+}
+
+/// Turns an input pattern into a pattern with mutable bindings.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __pure_cell_pat {
+    (_) => (_);
+    ($binding:ident) => (mut $binding);
+    (($pat:tt,)) => (($crate::__pure_cell_pat!($pat),));
+    (($($pat:tt),* $(,)?)) => (($($crate::__pure_cell_pat!($pat)),*));
+}
+
+/// Safe mechanism to read [`PureCell`](crate::PureCell) via a `const`
+/// expression.
+///
+/// The const expression borrows the state rather than taking it out of the
+/// cell, so reading part of a large state doesn't copy all of it.  The output
+/// can't borrow from the state.
+///
+/// Evaluates to a `Result` of the const expression's output.
+///
+/// # Errors
+/// Returns [`PoisonError`](crate::PoisonError) holding the input if the cell
+/// was poisoned by an earlier panicking update.
+///
+/// ```rust
+/// use pure_cell::{PureCell, pure_cell_read};
+///
+/// let cell = PureCell::new([15; 1024]);
+/// let index = 7;
+/// let value = pure_cell_read!(cell, index, |state: &[u32; 1024], i: usize| -> u32 {
+///     state[i]
+/// });
+/// assert_eq!(value.unwrap(), 15);
+/// ```
+///
+/// ```rust,compile_fail
+/// use pure_cell::{PureCell, pure_cell_read};
+///
+/// let cell = PureCell::new([15; 1024]);
+/// let value = pure_cell_read!(cell, (), |state: &[u32; 1024], _args: ()| -> &u32 {
+///     &state[7]
+/// });
+/// ```
+#[macro_export]
+macro_rules! pure_cell_read {
+    (
+        $pure_cell:expr,
+        $input:expr,
+        $(<$($gen:ident $(: $bound:ident $(+ $bounds:ident)*)?),* $(,)?>)?
+        |$state:ident: &$ty:ty, $args:tt: $argty:ty| -> $ret:ty $block:block
+    ) => ({
+        #[inline(always)]
+        const fn const_fn<$($($gen $(: $bound $(+ $bounds)*)?),*)?>(
+            $state: &$ty,
+            args: $argty,
+        ) -> $ret {
+            #[allow(unused_mut)]
+            let $crate::__pure_cell_pat!($args) = args;
+            $block
+        }
+        let cell = &$pure_cell;
+        let input = $input;
+        unsafe { $crate::PureAccess::with_ref(cell, input, const_fn) }
+    });
+}
+
+/// Declares thread local [`PureCell`](crate::PureCell)s, accessed through
+/// [`PureLocalKey`](crate::PureLocalKey).
+///
+/// Initializers must be const expressions.  The declared keys can be passed
+/// directly to [`pure_cell!()`] and [`pure_cell_read!()`].
+///
+/// ```rust
+/// use pure_cell::{pure_cell, pure_thread_local};
+///
+/// pure_thread_local! {
+///     /// Number of events handled on this thread
+///     static EVENTS: u32 = 0;
+/// }
+///
+/// let count = pure_cell!(EVENTS, 2, |state: u32, amount: u32| -> u32 {
+///     state += amount;
+///     state
+/// });
+/// assert_eq!(count.unwrap(), 2);
+/// assert_eq!(EVENTS.get_copy(), 2);
+///
+/// std::thread::spawn(|| assert_eq!(EVENTS.get_copy(), 0))
+///     .join()
+///     .unwrap();
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! pure_thread_local {
+    ($($(#[$attr:meta])* $vis:vis static $name:ident: $ty:ty = $init:expr;)*) => (
+        $(
+            $(#[$attr])*
+            $vis static $name: $crate::PureLocalKey<$ty> = {
+                $crate::__thread_local! {
+                    static KEY: $crate::PureCell<$ty> = {
+                        const INIT: $crate::PureCell<$ty> =
+                            $crate::PureCell::new($init);
+                        INIT
+                    };
+                }
+                $crate::PureLocalKey::new(&KEY)
+            };
+        )*
+    );
+}
@@ -0,0 +1,425 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+use core::{
+    cell::{Cell, UnsafeCell},
+    fmt::{self, Debug, Formatter},
+    hint,
+    mem::{self, MaybeUninit},
+    ops::Deref,
+    ptr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::{PureCell, SyncPureCell};
+
+/// The initializer hasn't run yet.
+const UNINIT: u8 = 0;
+/// The initializer is running, possibly on another thread.
+const INITIALIZING: u8 = 1;
+/// The cell holds a valid `SyncPureCell`.
+const READY: u8 = 2;
+/// The initializer panicked.
+const FAILED: u8 = 3;
+
+/// Ends initialization of the cell when dropped, including while unwinding.
+struct InitGuard<'a>(&'a Cell<bool>);
+
+impl Drop for InitGuard<'_> {
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+/// A [`PureCell`] that's initialized on first use.
+///
+/// Once initialized, the cell can be updated with
+/// [`pure_cell!()`](crate::pure_cell) and the other macros through the
+/// `&PureCell` returned by [`get_or_init()`](PureOnceCell::get_or_init).
+/// The initializer doesn't have to be const code; initializing the cell from
+/// within its own initializer panics instead.
+///
+/// ```rust
+/// use pure_cell::{PureOnceCell, pure_cell};
+///
+/// const fn squares() -> [u32; 8] {
+///     let mut table = [0; 8];
+///     let mut i = 0;
+///     while i < table.len() {
+///         table[i] = (i * i) as u32;
+///         i += 1;
+///     }
+///     table
+/// }
+///
+/// let cache = PureOnceCell::new();
+/// assert!(cache.get().is_none());
+///
+/// for _ in 0..2 {
+///     let table = cache.get_or_init(squares);
+///     pure_cell!(table, 7, |table: [u32; 8], i: usize| {
+///         table[i] += 1;
+///     })
+///     .unwrap();
+/// }
+/// assert_eq!(cache.into_inner().unwrap()[7], 51);
+/// ```
+pub struct PureOnceCell<T> {
+    initializing: Cell<bool>,
+    cell: UnsafeCell<Option<PureCell<T>>>,
+}
+
+impl<T> PureOnceCell<T> {
+    /// Creates a new, uninitialized `PureOnceCell`.
+    pub const fn new() -> Self {
+        Self {
+            initializing: Cell::new(false),
+            cell: UnsafeCell::new(None),
+        }
+    }
+
+    /// Returns the inner cell, or `None` if it isn't initialized yet.
+    pub fn get(&self) -> Option<&PureCell<T>> {
+        unsafe { (*self.cell.get()).as_ref() }
+    }
+
+    /// Returns a mutable reference to the inner cell, or `None` if it isn't
+    /// initialized yet.
+    pub fn get_mut(&mut self) -> Option<&mut PureCell<T>> {
+        self.cell.get_mut().as_mut()
+    }
+
+    /// Initializes the inner cell with `value`, or returns `value` back if
+    /// it's already initialized.
+    ///
+    /// # Errors
+    /// Returns `value` if the cell is already initialized.
+    ///
+    /// # Panics
+    /// If called from within the cell's initializer.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        if self.get().is_some() {
+            return Err(value);
+        }
+
+        self.init(|| value);
+        Ok(())
+    }
+
+    /// Returns the inner cell, initializing it with `f` if it isn't
+    /// initialized yet.
+    ///
+    /// If `f` panics, the panic is propagated and the cell stays
+    /// uninitialized.
+    ///
+    /// # Panics
+    /// If the cell is initialized from within `f`.
+    ///
+    /// ```rust,should_panic
+    /// use pure_cell::PureOnceCell;
+    ///
+    /// let cell = PureOnceCell::new();
+    /// cell.get_or_init(|| cell.get_or_init(|| 1).get_copy() + 1);
+    /// ```
+    pub fn get_or_init<F>(&self, f: F) -> &PureCell<T>
+    where
+        F: FnOnce() -> T,
+    {
+        match self.get() {
+            Some(cell) => cell,
+            None => self.init(f),
+        }
+    }
+
+    /// Consumes the `PureOnceCell`, returning the wrapped value, if
+    /// initialized.
+    ///
+    /// # Panics
+    /// If the inner cell was poisoned by a panicking update.
+    pub fn into_inner(self) -> Option<T> {
+        self.cell.into_inner().map(PureCell::into_inner)
+    }
+
+    /// Takes the wrapped value, if initialized, leaving the `PureOnceCell`
+    /// uninitialized.
+    ///
+    /// # Panics
+    /// If the inner cell was poisoned by a panicking update.
+    pub fn take(&mut self) -> Option<T> {
+        self.cell.get_mut().take().map(PureCell::into_inner)
+    }
+
+    #[cold]
+    fn init(&self, f: impl FnOnce() -> T) -> &PureCell<T> {
+        if self.initializing.replace(true) {
+            panic!("PureOnceCell initialized from within its own initializer");
+        }
+
+        let guard = InitGuard(&self.initializing);
+        let value = f();
+        drop(guard);
+
+        // No references into the `Option` exist while it's `None`
+        unsafe { &*(*self.cell.get()).get_or_insert(PureCell::new(value)) }
+    }
+}
+
+impl<T: Debug> Debug for PureOnceCell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PureOnceCell")
+            .field("cell", &self.get())
+            .finish()
+    }
+}
+
+impl<T> Default for PureOnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<T> for PureOnceCell<T> {
+    fn from(value: T) -> Self {
+        Self {
+            initializing: Cell::new(false),
+            cell: UnsafeCell::new(Some(PureCell::new(value))),
+        }
+    }
+}
+
+/// A [`PureCell`] that's initialized by `F` on first access.
+///
+/// Dereferences to the inner cell, and can be passed to
+/// [`pure_cell!()`](crate::pure_cell) and the other macros directly.  Like
+/// `PureCell`, it isn't `Sync`, so lazily computed global state goes in a
+/// `thread_local!()`, or a `static` [`SyncPureLazy`].
+///
+/// ```rust
+/// use pure_cell::{PureLazy, pure_cell};
+///
+/// thread_local! {
+///     static PRIMES: PureLazy<[u32; 8]> = PureLazy::new(|| {
+///         let mut primes = [0; 8];
+///         let found = (2..).filter(|n| (2..*n).all(|d| n % d != 0));
+///         for (prime, found) in primes.iter_mut().zip(found) {
+///             *prime = found;
+///         }
+///         primes
+///     });
+/// }
+///
+/// PRIMES.with(|primes| {
+///     let largest = pure_cell!(primes, 7, |primes: [u32; 8], i: usize| -> u32 {
+///         primes[i]
+///     })
+///     .unwrap();
+///     assert_eq!(largest, 19);
+/// });
+/// ```
+pub struct PureLazy<T, F = fn() -> T> {
+    once: PureOnceCell<T>,
+    init: Cell<Option<F>>,
+}
+
+impl<T, F> PureLazy<T, F> {
+    /// Creates a new `PureLazy` that's initialized by `init` on first access.
+    pub const fn new(init: F) -> Self {
+        Self {
+            once: PureOnceCell::new(),
+            init: Cell::new(Some(init)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> PureLazy<T, F> {
+    /// Returns the inner cell, initializing it if it isn't initialized yet.
+    ///
+    /// # Panics
+    /// If the initializer panics (now or on an earlier access), or accesses
+    /// this `PureLazy`.
+    pub fn force(this: &Self) -> &PureCell<T> {
+        this.once.get_or_init(|| match this.init.take() {
+            Some(init) => init(),
+            None => panic!("PureLazy poisoned by a panicking initializer"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for PureLazy<T, F> {
+    type Target = PureCell<T>;
+
+    fn deref(&self) -> &PureCell<T> {
+        Self::force(self)
+    }
+}
+
+impl<T: Debug, F> Debug for PureLazy<T, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PureLazy")
+            .field("cell", &self.once.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Default> Default for PureLazy<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+/// A [`SyncPureCell`] that's initialized by `F` on first access, which can be
+/// placed in a `static`.
+///
+/// Dereferences to the inner cell, and can be passed to
+/// [`pure_cell!()`](crate::pure_cell) and the other macros directly.  If
+/// another thread is running the initializer, the caller spins until it
+/// finishes, like accessing a locked `SyncPureCell`.
+///
+/// ```rust
+/// use std::thread;
+///
+/// use pure_cell::{pure_cell, SyncPureLazy};
+///
+/// static SQUARES: SyncPureLazy<[u32; 8]> = SyncPureLazy::new(|| {
+///     let mut squares = [0; 8];
+///     for (i, square) in squares.iter_mut().enumerate() {
+///         *square = (i * i) as u32;
+///     }
+///     squares
+/// });
+///
+/// let threads: Vec<_> = (0..4)
+///     .map(|i| {
+///         thread::spawn(move || {
+///             pure_cell!(SQUARES, i, |squares: [u32; 8], i: usize| {
+///                 squares[i] += 1;
+///             })
+///             .unwrap();
+///         })
+///     })
+///     .collect();
+///
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// assert_eq!(SQUARES.get_copy(), [1, 2, 5, 10, 16, 25, 36, 49]);
+/// ```
+pub struct SyncPureLazy<T, F = fn() -> T> {
+    state: AtomicU8,
+    cell: UnsafeCell<MaybeUninit<SyncPureCell<T>>>,
+    init: UnsafeCell<Option<F>>,
+}
+
+unsafe impl<T: Send, F: Send> Sync for SyncPureLazy<T, F> {}
+
+impl<T, F> SyncPureLazy<T, F> {
+    /// Creates a new `SyncPureLazy` that's initialized by `init` on first
+    /// access.
+    pub const fn new(init: F) -> Self {
+        Self {
+            state: AtomicU8::new(UNINIT),
+            cell: UnsafeCell::new(MaybeUninit::uninit()),
+            init: UnsafeCell::new(Some(init)),
+        }
+    }
+
+    /// Returns the inner cell, or `None` if it isn't initialized yet.
+    fn get(&self) -> Option<&SyncPureCell<T>> {
+        if self.state.load(Ordering::Acquire) != READY {
+            return None;
+        }
+
+        Some(unsafe { &*(*self.cell.get()).as_ptr() })
+    }
+}
+
+impl<T, F: FnOnce() -> T> SyncPureLazy<T, F> {
+    /// Returns the inner cell, initializing it if it isn't initialized yet.
+    ///
+    /// # Panics
+    /// If the initializer panics (now or on an earlier access).  An
+    /// initializer that accesses this `SyncPureLazy` deadlocks.
+    pub fn force(this: &Self) -> &SyncPureCell<T> {
+        loop {
+            if let Some(cell) = this.get() {
+                return cell;
+            }
+
+            match this.state.compare_exchange_weak(
+                UNINIT,
+                INITIALIZING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return this.init(),
+                Err(FAILED) => {
+                    panic!("SyncPureLazy poisoned by a panicking initializer")
+                }
+                Err(_) => hint::spin_loop(),
+            }
+        }
+    }
+
+    #[cold]
+    fn init(&self) -> &SyncPureCell<T> {
+        /// Marks the initializer as failed when dropped, which only happens
+        /// while unwinding.
+        struct Fail<'a>(&'a AtomicU8);
+
+        impl Drop for Fail<'_> {
+            fn drop(&mut self) {
+                self.0.store(FAILED, Ordering::Release);
+            }
+        }
+
+        // Only this thread accesses `init` and `cell` while initializing
+        let guard = Fail(&self.state);
+        let init = unsafe { (*self.init.get()).take() };
+        let value = match init {
+            Some(init) => init(),
+            None => unreachable!(),
+        };
+        let cell =
+            unsafe { &*(*self.cell.get()).write(SyncPureCell::new(value)) };
+        mem::forget(guard);
+        self.state.store(READY, Ordering::Release);
+        cell
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for SyncPureLazy<T, F> {
+    type Target = SyncPureCell<T>;
+
+    fn deref(&self) -> &SyncPureCell<T> {
+        Self::force(self)
+    }
+}
+
+impl<T, F> Drop for SyncPureLazy<T, F> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == READY {
+            unsafe { ptr::drop_in_place(self.cell.get_mut().as_mut_ptr()) }
+        }
+    }
+}
+
+impl<T: Debug, F> Debug for SyncPureLazy<T, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncPureLazy")
+            .field("cell", &self.get())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Default> Default for SyncPureLazy<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
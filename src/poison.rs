@@ -0,0 +1,59 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+use core::fmt::{Debug, Display, Formatter, Result};
+
+/// An error returned when updating a poisoned cell.
+///
+/// A cell is poisoned when an update panics part way through, since the value
+/// may have been moved out.  The input passed to the failed update was never
+/// consumed, and is handed back by this error.
+///
+/// Use [`PureCell::clear_poison()`](crate::PureCell::clear_poison) to recover.
+pub struct PoisonError<A> {
+    input: A,
+}
+
+impl<A> PoisonError<A> {
+    /// Creates a `PoisonError` holding the unconsumed update input.
+    pub fn new(input: A) -> Self {
+        Self { input }
+    }
+
+    /// Consumes this error, returning the unconsumed update input.
+    pub fn into_inner(self) -> A {
+        self.input
+    }
+
+    /// Returns a reference to the unconsumed update input.
+    pub fn get_ref(&self) -> &A {
+        &self.input
+    }
+
+    /// Returns a mutable reference to the unconsumed update input.
+    pub fn get_mut(&mut self) -> &mut A {
+        &mut self.input
+    }
+}
+
+impl<A> Debug for PoisonError<A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+
+impl<A> Display for PoisonError<A> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.write_str("poisoned cell: an earlier update panicked")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A> std::error::Error for PoisonError<A> {}
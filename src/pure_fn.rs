@@ -0,0 +1,97 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+/// A named pure state transition, for use with
+/// [`PureCell::update()`](crate::PureCell::update).
+///
+/// Trait methods can't be `const fn`, so implement this trait with
+/// [`impl_pure_fn!()`](crate::impl_pure_fn) (or `#[pure_fn]` with the `macros`
+/// feature), which checks that the transition calls a `const fn`.
+///
+/// ```rust
+/// use pure_cell::{PureCell, impl_pure_fn};
+///
+/// const fn increment(count: u32, amount: u32) -> (u32, u32) {
+///     (count + amount, count + amount)
+/// }
+///
+/// impl_pure_fn! {
+///     /// Adds to the count, returning the new count.
+///     struct Increment = increment as fn(u32, u32) -> (u32, u32);
+/// }
+///
+/// assert_eq!(increment(1, 2), (3, 3));
+///
+/// let cell = PureCell::new(15);
+/// assert_eq!(cell.update::<Increment>(2).unwrap(), 17);
+/// assert_eq!(cell.update::<Increment>(3).unwrap(), 20);
+/// ```
+///
+/// # Safety
+/// [`PureFn::call()`] must not access any [`PureCell`](crate::PureCell), or
+/// yield to other code.  Only calling a `const fn` guarantees this.
+pub unsafe trait PureFn<State> {
+    /// Input passed along with the state
+    type Args;
+    /// Output of the transition
+    type Output;
+
+    /// Transitions `state` to a new state, returning it with the output.
+    fn call(state: State, args: Self::Args) -> (State, Self::Output);
+}
+
+/// A named pure state transition that updates the state in place, for use with
+/// [`PureCell::update_in_place()`](crate::PureCell::update_in_place).
+///
+/// Unlike [`PureFn`], the state is never moved out of the cell, so large
+/// states aren't copied.  Requires the `const-mut-refs` feature (Rust 1.83 or
+/// later).  Implement with the `&mut` form of
+/// [`impl_pure_fn!()`](crate::impl_pure_fn).
+///
+/// ```rust
+/// use pure_cell::{PureCell, impl_pure_fn};
+///
+/// struct Buffer {
+///     data: [u8; 4096],
+///     len: usize,
+/// }
+///
+/// const fn push(buffer: &mut Buffer, byte: u8) -> bool {
+///     if buffer.len == buffer.data.len() {
+///         return false;
+///     }
+///     buffer.data[buffer.len] = byte;
+///     buffer.len += 1;
+///     true
+/// }
+///
+/// impl_pure_fn! {
+///     /// Appends a byte, returning `false` if the buffer is full.
+///     struct Push = push as fn(&mut Buffer, u8) -> bool;
+/// }
+///
+/// let cell = PureCell::new(Buffer { data: [0; 4096], len: 0 });
+/// assert!(cell.update_in_place::<Push>(7).unwrap());
+/// assert_eq!(cell.into_inner().data[..2], [7, 0]);
+/// ```
+///
+/// # Safety
+/// [`PureFnMut::call()`] must not access any [`PureCell`](crate::PureCell),
+/// or yield to other code.  Only calling a `const fn` guarantees this.
+#[cfg(feature = "const-mut-refs")]
+pub unsafe trait PureFnMut<State: ?Sized> {
+    /// Input passed along with the state
+    type Args;
+    /// Output of the transition
+    type Output;
+
+    /// Updates `state` in place, returning the output.
+    fn call(state: &mut State, args: Self::Args) -> Self::Output;
+}
@@ -0,0 +1,270 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+use core::{
+    fmt::{self, Debug, Formatter},
+    iter::FusedIterator,
+    mem::{self, ManuallyDrop},
+    ops::Range,
+};
+
+use crate::{PoisonError, PureCell};
+
+impl<T, const N: usize> PureCell<[T; N]> {
+    /// Returns this cell as a cell of a slice, for per-element access.
+    pub fn as_slice(&self) -> &PureCell<[T]> {
+        self
+    }
+}
+
+impl<T> PureCell<[T]> {
+    /// Returns the number of elements in the slice.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed.
+    pub fn len(&self) -> usize {
+        self.assert_unborrowed();
+
+        let slice: &[T] = unsafe { &*self.value.get() };
+
+        slice.len()
+    }
+
+    /// Returns `true` if the slice has no elements.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a handle to the element at `index`, or `None` if it's out of
+    /// bounds.
+    ///
+    /// See [`PureElement`] for an example.
+    ///
+    /// # Panics
+    /// If called while the value is borrowed.
+    pub fn element(&self, index: usize) -> Option<PureElement<'_, T>> {
+        if index >= self.len() {
+            return None;
+        }
+
+        Some(PureElement { cell: self, index })
+    }
+
+    /// Returns an iterator over handles to each element, the `PureCell`
+    /// equivalent of [`Cell::as_slice_of_cells()`](core::cell::Cell).
+    ///
+    /// # Panics
+    /// If called while the value is borrowed.
+    pub fn elements(&self) -> Elements<'_, T> {
+        Elements {
+            cell: self,
+            range: 0..self.len(),
+        }
+    }
+}
+
+/// A handle to one element of a [`PureCell`] slice or array, which can be
+/// passed to [`pure_cell!()`](crate::pure_cell) and
+/// [`pure_cell_read!()`](crate::pure_cell_read).
+///
+/// Updates through the handle only move the element out of the cell and back,
+/// rather than the whole slice.  If an update panics, the whole cell is
+/// poisoned.
+///
+/// ```rust
+/// use pure_cell::{PureCell, pure_cell};
+///
+/// let table = PureCell::new([1, 2, 3, 4]);
+/// let element = table.as_slice().element(2).unwrap();
+/// let value = pure_cell!(element, 2, |x: u32, factor: u32| -> u32 {
+///     x *= factor;
+///     x
+/// });
+/// assert_eq!(value.unwrap(), 6);
+///
+/// for element in table.as_slice().elements() {
+///     pure_cell!(element, (), |x: u32, _: ()| {
+///         x += 1;
+///     })
+///     .unwrap();
+/// }
+/// assert_eq!(table.into_inner(), [2, 3, 7, 5]);
+///
+/// let slice: &PureCell<[u32]> = &PureCell::new([0; 4]);
+/// assert!(slice.element(4).is_none());
+/// ```
+pub struct PureElement<'a, T> {
+    cell: &'a PureCell<[T]>,
+    index: usize,
+}
+
+impl<T> PureElement<'_, T> {
+    /// Returns the index of the element within the slice.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Replaces the element with `value`, and returns the old element.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn replace(&self, value: T) -> T {
+        self.cell.assert_ready();
+
+        unsafe {
+            mem::replace(&mut (**self.cell.value.get())[self.index], value)
+        }
+    }
+
+    /// Sets the element, dropping the old element.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn set(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    /// Returns a copy of the element.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn get_copy(&self) -> T
+    where
+        T: Copy,
+    {
+        self.cell.assert_ready();
+
+        unsafe { (**self.cell.value.get())[self.index] }
+    }
+
+    /// See [`PureCell::with()`].
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with()`].
+    pub unsafe fn with<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+    {
+        let index = self.index;
+
+        self.cell.with(input, |slice, input| {
+            let element: *mut T = &mut slice[index];
+            f(&mut *element.cast::<ManuallyDrop<T>>(), input)
+        })
+    }
+
+    /// See [`PureCell::with_ref()`].
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// See [`PureCell::with_ref()`].
+    pub unsafe fn with_ref<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&T, A) -> R,
+    {
+        let index = self.index;
+
+        self.cell
+            .with_ref(input, |slice, input| f(&slice[index], input))
+    }
+
+    /// Returns the address of the slice's cell, for
+    /// [`PureAccess::addr()`](crate::PureAccess::addr),
+    /// since updating an element borrows the whole slice.
+    pub(crate) fn addr(&self) -> *const () {
+        self.cell.addr()
+    }
+}
+
+impl<T> Clone for PureElement<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for PureElement<'_, T> {}
+
+impl<T> Debug for PureElement<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PureElement")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Iterator over handles to each element of a [`PureCell`] slice, returned by
+/// [`PureCell::elements()`].
+pub struct Elements<'a, T> {
+    cell: &'a PureCell<[T]>,
+    range: Range<usize>,
+}
+
+impl<'a, T> Iterator for Elements<'a, T> {
+    type Item = PureElement<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cell = self.cell;
+
+        self.range.next().map(|index| PureElement { cell, index })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for Elements<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let cell = self.cell;
+
+        self.range
+            .next_back()
+            .map(|index| PureElement { cell, index })
+    }
+}
+
+impl<T> ExactSizeIterator for Elements<'_, T> {}
+
+impl<T> FusedIterator for Elements<'_, T> {}
+
+impl<T> Clone for Elements<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            cell: self.cell,
+            range: self.range.clone(),
+        }
+    }
+}
+
+impl<T> Debug for Elements<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Elements")
+            .field("range", &self.range)
+            .finish_non_exhaustive()
+    }
+}
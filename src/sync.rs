@@ -0,0 +1,380 @@
+// Pure Cell
+// Copyright © 2022 Jeron Aldaron Lau.
+//
+// Licensed under any of:
+// - Apache License, Version 2.0 (https://www.apache.org/licenses/LICENSE-2.0)
+// - MIT License (https://mit-license.org/)
+// - Boost Software License, Version 1.0 (https://www.boost.org/LICENSE_1_0.txt)
+// At your choosing (See accompanying files LICENSE_APACHE_2_0.txt,
+// LICENSE_MIT.txt and LICENSE_BOOST_1_0.txt).
+
+use core::{
+    cell::UnsafeCell,
+    fmt::{self, Debug, Formatter},
+    hint,
+    mem::{self, ManuallyDrop},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use crate::PoisonError;
+
+/// The cell holds a valid value.
+const READY: u8 = 0;
+/// The value is being accessed, possibly from another thread.
+const LOCKED: u8 = 1;
+/// An update panicked, so the value may have been moved out.
+const POISONED: u8 = 2;
+
+/// Stores a new state when dropped, ending access to the cell.
+struct Unlock<'a>(&'a AtomicU8, u8);
+
+impl Drop for Unlock<'_> {
+    fn drop(&mut self) {
+        self.0.store(self.1, Ordering::Release);
+    }
+}
+
+/// A thread-safe [`PureCell`](crate::PureCell), which can be placed in a
+/// `static`.
+///
+/// Access is guarded by an atomic flag rather than a mutex, so no operating
+/// system support is required.  If the flag is already set, the caller spins
+/// until the access holding it finishes.  Since updates made with
+/// [`pure_cell!()`](crate::pure_cell) can only run const code, the flag is
+/// never held for long.  Closures passed to
+/// [`modify()`](crate::PureAccess::modify) hold it until they return, and
+/// accessing the cell from within one deadlocks.
+///
+/// Spinning means an access from an interrupt handler (or signal handler)
+/// deadlocks if the code it interrupted was accessing the same cell.  Don't
+/// share a `SyncPureCell` between interrupt handlers and the code they
+/// interrupt on the same core; use a
+/// [`CriticalPureCell`](crate::CriticalPureCell) instead.
+///
+/// ```rust
+/// use std::thread;
+///
+/// use pure_cell::{pure_cell, SyncPureCell};
+///
+/// static COUNTER: SyncPureCell<u32> = SyncPureCell::new(0);
+///
+/// let threads: Vec<_> = (0..4)
+///     .map(|_| {
+///         thread::spawn(|| {
+///             for _ in 0..100 {
+///                 pure_cell!(COUNTER, 1, |state: u32, amount: u32| {
+///                     state += amount;
+///                 })
+///                 .unwrap();
+///             }
+///         })
+///     })
+///     .collect();
+///
+/// for thread in threads {
+///     thread.join().unwrap();
+/// }
+/// assert_eq!(COUNTER.get_copy(), 400);
+/// ```
+pub struct SyncPureCell<T> {
+    state: AtomicU8,
+    value: UnsafeCell<ManuallyDrop<T>>,
+}
+
+unsafe impl<T: Send> Sync for SyncPureCell<T> {}
+
+impl<T> SyncPureCell<T> {
+    /// Creates a new `SyncPureCell` containing the given value.
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU8::new(READY),
+            value: UnsafeCell::new(ManuallyDrop::new(value)),
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn get_mut(&mut self) -> &mut T {
+        if *self.state.get_mut() == POISONED {
+            poisoned();
+        }
+
+        self.value.get_mut()
+    }
+
+    /// Consumes the cell, returning the wrapped value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn into_inner(mut self) -> T {
+        if *self.state.get_mut() == POISONED {
+            poisoned();
+        }
+
+        let mut this = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::take(this.value.get_mut()) }
+    }
+
+    /// Replaces the contained value with `value`, and returns the old value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn replace(&self, value: T) -> T {
+        let result = unsafe {
+            self.with(value, |state, value| mem::replace(&mut **state, value))
+        };
+
+        result.unwrap_or_else(|_| poisoned())
+    }
+
+    /// Sets the contained value, dropping the old value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn set(&self, value: T) {
+        drop(self.replace(value));
+    }
+
+    /// Takes the contained value, leaving `Default::default()` in its place.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn take(&self) -> T
+    where
+        T: Default,
+    {
+        self.replace(T::default())
+    }
+
+    /// Returns a copy of the contained value.
+    ///
+    /// # Panics
+    /// If the cell was poisoned by a panicking update.
+    pub fn get_copy(&self) -> T
+    where
+        T: Copy,
+    {
+        let result = unsafe { self.with_ref((), |value, ()| *value) };
+
+        result.unwrap_or_else(|_| poisoned())
+    }
+
+    /// Returns `true` if an earlier update panicked, poisoning the cell.
+    pub fn is_poisoned(&self) -> bool {
+        self.state.load(Ordering::Acquire) == POISONED
+    }
+
+    /// Stores `value` in the cell and clears the poisoned state.
+    ///
+    /// If the cell wasn't poisoned, the old value is dropped.
+    ///
+    /// ```rust
+    /// use std::{
+    ///     panic::{self, AssertUnwindSafe},
+    ///     thread,
+    /// };
+    ///
+    /// use pure_cell::{PureAccess, SyncPureCell, pure_cell};
+    ///
+    /// static CELL: SyncPureCell<u32> = SyncPureCell::new(15);
+    ///
+    /// let update = thread::spawn(|| {
+    ///     pure_cell!(CELL, 0, |state: u32, divisor: u32| {
+    ///         state /= divisor;
+    ///     })
+    /// });
+    /// assert!(update.join().is_err());
+    /// assert!(CELL.is_poisoned());
+    ///
+    /// let error = pure_cell!(CELL, 2, |state: u32, amount: u32| {
+    ///     state += amount;
+    /// })
+    /// .unwrap_err();
+    /// assert_eq!(error.into_inner(), 2);
+    /// assert!(CELL.modify(|state| *state += 2).is_err());
+    /// let replace = panic::catch_unwind(AssertUnwindSafe(|| CELL.replace(0)));
+    /// assert!(replace.is_err());
+    ///
+    /// CELL.clear_poison(10);
+    /// assert!(!CELL.is_poisoned());
+    /// let doubled = CELL.modify(|state| {
+    ///     *state *= 2;
+    ///     *state
+    /// });
+    /// assert_eq!(doubled.unwrap(), 20);
+    /// assert_eq!(CELL.replace(1), 20);
+    /// assert_eq!(CELL.get_copy(), 1);
+    /// ```
+    pub fn clear_poison(&self, value: T) {
+        let was_ready = loop {
+            let state = self.state.load(Ordering::Relaxed);
+
+            if state == LOCKED {
+                hint::spin_loop();
+                continue;
+            }
+
+            if self
+                .state
+                .compare_exchange_weak(
+                    state,
+                    LOCKED,
+                    Ordering::Acquire,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                break state == READY;
+            }
+        };
+
+        let old = unsafe {
+            mem::replace(&mut *self.value.get(), ManuallyDrop::new(value))
+        };
+
+        self.state.store(READY, Ordering::Release);
+
+        if was_ready {
+            drop(ManuallyDrop::into_inner(old));
+        }
+    }
+
+    /// Update cell, passing `input` along to the closure.
+    ///
+    /// Spins while the cell is accessed from another thread.  If the closure
+    /// panics, the cell is poisoned and its value is never dropped (it may
+    /// have been moved out by the closure).
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// Sound to use so long as you follow these rules in the closure:
+    ///
+    ///  - Must not yield to other code (usually async)
+    ///  - Must not access this cell (recursively calling `Self::with()`,
+    ///    `Self::clear_poison()`, etc.), which would deadlock
+    ///  - Must leave a valid value in the cell when returning normally
+    pub unsafe fn with<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&mut ManuallyDrop<T>, A) -> R,
+    {
+        if !self.lock() {
+            return Err(PoisonError::new(input));
+        }
+
+        let guard = Unlock(&self.state, POISONED);
+        let output = f(&mut *self.value.get(), input);
+        mem::forget(guard);
+        self.state.store(READY, Ordering::Release);
+        Ok(output)
+    }
+
+    /// Read cell, passing `input` along to the closure.
+    ///
+    /// Spins while the cell is accessed from another thread.
+    ///
+    /// # Errors
+    /// Returns [`PoisonError`] holding `input` if the cell was poisoned by an
+    /// earlier panicking update.
+    ///
+    /// # Safety
+    /// Sound to use so long as you follow these rules in the closure:
+    ///
+    ///  - Must not yield to other code (usually async)
+    ///  - Must not access this cell (calling `Self::with()`,
+    ///    `Self::clear_poison()`, etc.), which would deadlock
+    pub unsafe fn with_ref<A, R, F>(
+        &self,
+        input: A,
+        f: F,
+    ) -> Result<R, PoisonError<A>>
+    where
+        F: FnOnce(&T, A) -> R,
+    {
+        if !self.lock() {
+            return Err(PoisonError::new(input));
+        }
+
+        let _guard = Unlock(&self.state, READY);
+        Ok(f(&*self.value.get(), input))
+    }
+
+    /// Spins until the cell is locked, returning `false` if it's poisoned.
+    fn lock(&self) -> bool {
+        loop {
+            match self.state.compare_exchange_weak(
+                READY,
+                LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(POISONED) => return false,
+                Err(_) => hint::spin_loop(),
+            }
+        }
+    }
+
+    /// Returns the address of the cell, for
+    /// [`PureAccess::addr()`](crate::PureAccess::addr).
+    pub(crate) fn addr(&self) -> *const () {
+        let cell: *const Self = self;
+
+        cell.cast()
+    }
+}
+
+impl<T> Drop for SyncPureCell<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == POISONED {
+            return;
+        }
+
+        unsafe { ManuallyDrop::drop(self.value.get_mut()) }
+    }
+}
+
+impl<T: Debug> Debug for SyncPureCell<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("SyncPureCell");
+
+        match self.state.compare_exchange(
+            READY,
+            LOCKED,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => {
+                let _guard = Unlock(&self.state, READY);
+                f.field("value", unsafe { &**self.value.get() })
+            }
+            Err(POISONED) => f.field("value", &format_args!("<poisoned>")),
+            Err(_) => f.field("value", &format_args!("<locked>")),
+        };
+        f.finish()
+    }
+}
+
+impl<T: Default> Default for SyncPureCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for SyncPureCell<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+fn poisoned() -> ! {
+    panic!("SyncPureCell poisoned by a panicking update")
+}